@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // gRPC のコードは grpc feature が有効な場合のみ生成する
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/event_store.proto");
+        let fds = protox::compile(["event_store.proto"], ["proto"])?;
+        tonic_build::configure().compile_fds(fds)?;
+    }
+    Ok(())
+}
@@ -6,13 +6,22 @@ use crate::error::Result;
 /// are changed together in response to commands.
 pub trait Aggregate: std::fmt::Debug + Send + Sync {
     type Command;
-    type Event: Clone + std::fmt::Debug + Send + Sync + serde::Serialize + serde::de::DeserializeOwned;
+    type Event: Clone + std::fmt::Debug + Send + Sync + serde::Serialize + serde::de::DeserializeOwned + EventType;
 
     /// Initializes an aggregate with the given identifier.
     ///
     /// This method is called to create a new instance of an aggregate root
     /// with a default state.
     fn init(id: String) -> Self;
+
+    /// Returns the name identifying the aggregate type, e.g. for stream namespacing.
+    ///
+    /// The name is persisted in every [`StreamId`](crate::store::stream::StreamId),
+    /// so it must not change once events are stored. The derive sets it with
+    /// `#[aggregate(name = "...")]`.
+    fn aggregate_type() -> &'static str
+    where
+        Self: Sized;
 }
 
 /// Handles a command, returning events.
@@ -25,6 +34,59 @@ pub trait Handle<C>: Aggregate {
     fn handle(&self, cmd: C) -> Result<Vec<Self::Event>, Self::Error>;
 }
 
+/// Persisted name and version of an event, configured with `#[event(name = "...", version = N)]`.
+///
+/// Events are stored under their name rather than the Rust variant identifier,
+/// so variants can be renamed without breaking history. `#[serde(rename)]` and
+/// `#[serde(rename_all)]` are taken into account, while internally, adjacently
+/// and untagged enums are rejected by the derive, as are duplicate names:
+///
+/// ```compile_fail
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Clone, Debug, Serialize, Deserialize, tsuzuri::Event)]
+/// enum AccountEvent {
+///     #[event(name = "account_opened")]
+///     Opened,
+///     #[event(name = "account_opened")]
+///     Reopened,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Clone, Debug, Serialize, Deserialize, tsuzuri::Event)]
+/// #[serde(tag = "type")]
+/// enum AccountEvent {
+///     Opened,
+///     Closed,
+/// }
+/// ```
+pub trait EventType {
+    /// Returns the name the event is stored under.
+    fn event_type(&self) -> &'static str;
+
+    /// Returns the schema version of the event. Defaults to 1.
+    fn event_version(&self) -> u32;
+
+    /// Resolves a stored event name to the serde tag of the variant it was written from.
+    fn variant_of(event_type: &str) -> Option<&'static str>
+    where
+        Self: Sized;
+}
+
+/// Name of a command, configured with `#[command(name = "...")]`.
+///
+/// The command bus accepts commands keyed by their name.
+pub trait CommandType {
+    /// Returns the name of the command.
+    fn command_type(&self) -> &'static str;
+
+    /// Resolves a command name to the serde tag of its variant.
+    fn variant_of(command_type: &str) -> Option<&'static str>
+    where
+        Self: Sized;
+}
+
 /// Applies an event, updating the aggregate state.
 ///
 /// Events modify aggregate state, and are emitted as the result of commands.
@@ -47,4 +109,8 @@ where
     fn init(id: String) -> Self {
         State(T::init(id))
     }
+
+    fn aggregate_type() -> &'static str {
+        T::aggregate_type()
+    }
 }
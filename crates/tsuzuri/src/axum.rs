@@ -0,0 +1,459 @@
+//! Integration with the axum web framework.
+//!
+//! [`router`] exposes every aggregate registered on the
+//! [`TsuzuriBuilder`](crate::TsuzuriBuilder) over HTTP:
+//!
+//! - `POST /{aggregate}/{id}/commands` dispatches the JSON command in the body,
+//!   e.g. `{"DepositFunds": {"amount": 100}}`.
+//! - `GET /{aggregate}/{id}/events` returns the events of the aggregate. The
+//!   optional `from` query parameter skips events before that sequence.
+//!
+//! Hand-written handlers can use the [`TsuzuriHandle`] and [`Metadata`]
+//! extractors, and return the tsuzuri errors directly: they are rendered as
+//! RFC 7807 `application/problem+json` responses.
+
+use crate::{
+    command_bus::CommandBusError, error::AggregateError, metadata::EventMetadata, store::payload::Payload,
+    store::sync::error::StoreError, Tsuzuri,
+};
+use ::axum::{
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, convert::Infallible, fmt::Debug, ops::Deref, sync::Arc};
+use time::format_description::well_known::Rfc3339;
+
+/// Builds a router serving commands and event reads for the registered aggregates.
+pub fn router<Q>(tsuzuri: Arc<Tsuzuri<Q>>) -> Router
+where
+    Q: Send + Sync + 'static,
+{
+    Router::new()
+        .route("/{aggregate}/{id}/commands", post(execute_command::<Q>))
+        .route("/{aggregate}/{id}/events", get(read_events::<Q>))
+        .with_state(tsuzuri)
+}
+
+async fn execute_command<Q>(
+    State(tsuzuri): State<Arc<Tsuzuri<Q>>>,
+    Path((aggregate, id)): Path<(String, String)>,
+    Metadata(metadata): Metadata,
+    Json(cmd): Json<Value>,
+) -> Result<StatusCode, CommandBusError>
+where
+    Q: Send + Sync + 'static,
+{
+    tsuzuri.dispatch_with_metadata(&aggregate, &id, cmd, metadata).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    #[serde(default)]
+    from: usize,
+}
+
+/// An event as returned by `GET /{aggregate}/{id}/events`.
+#[derive(Debug, Serialize)]
+struct EventResponse {
+    event_id: String,
+    id: String,
+    sequence: usize,
+    event: Value,
+    metadata: EventMetadata,
+    created_at: String,
+}
+
+impl TryFrom<Payload> for EventResponse {
+    type Error = serde_json::Error;
+
+    fn try_from(payload: Payload) -> Result<Self, Self::Error> {
+        let decode = |bytes: &[u8]| serde_json::from_slice::<Value>(bytes);
+        Ok(EventResponse {
+            event_id: payload.event_id.to_string(),
+            event: decode(&payload.bytes)?,
+            metadata: payload.event_metadata()?,
+            created_at: payload.created_at.format(&Rfc3339).unwrap_or_default(),
+            id: payload.id,
+            sequence: payload.sequence,
+        })
+    }
+}
+
+async fn read_events<Q>(
+    State(tsuzuri): State<Arc<Tsuzuri<Q>>>,
+    Path((aggregate, id)): Path<(String, String)>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<Vec<EventResponse>>, CommandBusError>
+where
+    Q: Send + Sync + 'static,
+{
+    let Some(stream) = tsuzuri.command_bus().stream_id(&aggregate, &id) else {
+        return Err(CommandBusError::UnknownAggregate(aggregate));
+    };
+    let payloads = tsuzuri
+        .es_read()
+        .read_to_latest(&stream, query.from)
+        .await
+        .map_err(AggregateError::from)?;
+    let events = payloads
+        .into_iter()
+        .map(EventResponse::try_from)
+        .collect::<Result<_, _>>()
+        .map_err(AggregateError::Deserialize)?;
+    Ok(Json(events))
+}
+
+/// Extracts the shared [`Tsuzuri`] from the router state.
+///
+/// The state must provide an `Arc<Tsuzuri<Q>>` through [`FromRef`].
+pub struct TsuzuriHandle<Q>(pub Arc<Tsuzuri<Q>>);
+
+impl<Q> Deref for TsuzuriHandle<Q> {
+    type Target = Tsuzuri<Q>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S, Q> FromRequestParts<S> for TsuzuriHandle<Q>
+where
+    Arc<Tsuzuri<Q>>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(TsuzuriHandle(Arc::from_ref(state)))
+    }
+}
+
+/// Headers with this prefix are copied into the command metadata without the prefix.
+const METADATA_HEADER_PREFIX: &str = "x-metadata-";
+
+/// Extracts the command metadata passed to
+/// [`execute_with_metadata`](Tsuzuri::execute_with_metadata) from the request headers.
+///
+/// - `x-correlation-id` sets the correlation id, falling back to `x-request-id`.
+/// - `x-causation-id`, `x-actor` and `x-tenant-id` set the causation id, actor and tenant.
+/// - `traceparent` and `tracestate` set the trace context.
+/// - `x-metadata-{key}` headers set the field named `{key}`, or the extension of that name.
+///
+/// Headers that are not valid UTF-8 are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct Metadata(pub EventMetadata);
+
+impl From<&HeaderMap> for Metadata {
+    fn from(headers: &HeaderMap) -> Self {
+        let header = |name: &str| Some(headers.get(name)?.to_str().ok()?.to_string());
+        let extensions: HashMap<_, _> = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let key = name.as_str().strip_prefix(METADATA_HEADER_PREFIX)?;
+                Some((key.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+
+        let metadata = EventMetadata::from(extensions);
+        Metadata(EventMetadata {
+            correlation_id: header("x-correlation-id")
+                .or_else(|| header("x-request-id"))
+                .or(metadata.correlation_id),
+            causation_id: header("x-causation-id").or(metadata.causation_id),
+            actor: header("x-actor").or(metadata.actor),
+            tenant: header("x-tenant-id").or(metadata.tenant),
+            traceparent: header("traceparent").or(metadata.traceparent),
+            tracestate: header("tracestate").or(metadata.tracestate),
+            ..metadata
+        })
+    }
+}
+
+impl<S> FromRequestParts<S> for Metadata
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Metadata::from(&parts.headers))
+    }
+}
+
+/// An RFC 7807 problem details response.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Additional members, such as the `rejection` returned by the aggregate.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, detail: impl Into<Option<String>>) -> Self {
+        Self {
+            type_: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn extension(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.extensions.insert(key.into(), value);
+        self
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response
+    }
+}
+
+impl IntoResponse for StoreError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            StoreError::Conflict { .. } => StatusCode::CONFLICT,
+            ref err if err.is_not_found() => StatusCode::NOT_FOUND,
+            _ => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        Problem::new(status, self.to_string()).into_response()
+    }
+}
+
+impl<E> IntoResponse for AggregateError<E>
+where
+    E: Serialize + Debug,
+{
+    fn into_response(self) -> Response {
+        match self {
+            AggregateError::UserError(rejection) => {
+                let rejection = serde_json::to_value(&rejection).unwrap_or_else(|_| format!("{rejection:?}").into());
+                Problem::new(StatusCode::UNPROCESSABLE_ENTITY, "The command was rejected".to_string())
+                    .extension("rejection", rejection)
+                    .into_response()
+            }
+            AggregateError::Store(err) => err.into_response(),
+            err => Problem::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+        }
+    }
+}
+
+impl IntoResponse for CommandBusError {
+    fn into_response(self) -> Response {
+        match self {
+            CommandBusError::UnknownAggregate(_) => {
+                Problem::new(StatusCode::NOT_FOUND, self.to_string()).into_response()
+            }
+            CommandBusError::InvalidCommand(_) => {
+                Problem::new(StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+            CommandBusError::Aggregate(err) => err.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::{
+            stream::StreamId,
+            sync::{event_store::EventStore, memory_store::MemoryStore, reader::Reader, writer::Writer},
+        },
+        tests::BankAccount,
+        TsuzuriBuilder,
+    };
+    use ::axum::{body::Body, http::Request};
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use std::collections::BTreeSet;
+    use tower::ServiceExt;
+
+    fn post(uri: &str, body: Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new()))
+            .register::<BankAccount>()
+            .build();
+        let app = router(Arc::new(tsuzuri));
+
+        let response = app
+            .clone()
+            .oneshot(post("/BankAccount/axum_1_A/commands", json!({"OpenAccount": {}})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                "/BankAccount/axum_1_A/commands",
+                json!({"WithdrawFunds": {"amount": 10}}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = app
+            .clone()
+            .oneshot(post("/Customer/axum_1_A/commands", json!({"OpenAccount": {}})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::get("/BankAccount/axum_1_A/events")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events[0]["sequence"], 1);
+        assert_eq!(events[0]["event"], json!({"OpenedAccount": {}}));
+        assert_eq!(events[0]["metadata"]["event_type"], "OpenedAccount");
+        assert_eq!(events[0]["metadata"]["event_id"], events[0]["event_id"]);
+    }
+
+    /// Reads from a memory store and fails every write with `error`.
+    #[derive(Clone)]
+    struct FailingWrites {
+        store: MemoryStore,
+        error: fn() -> StoreError,
+    }
+
+    #[async_trait::async_trait]
+    impl Reader for FailingWrites {
+        async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+            self.store.read(stream, seq).await
+        }
+
+        async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+            self.store.read_to(stream, from, to).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Writer for FailingWrites {
+        async fn append(&self, _: &StreamId, _: usize, _: Vec<Payload>) -> Result<(), StoreError> {
+            Err((self.error)())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router_store_errors() {
+        let app = |error: fn() -> StoreError| {
+            let store = FailingWrites {
+                store: MemoryStore::new(),
+                error,
+            };
+            let tsuzuri = TsuzuriBuilder::new(EventStore::new(store))
+                .register::<BankAccount>()
+                .build();
+            router(Arc::new(tsuzuri))
+        };
+        let open = || post("/BankAccount/axum_3_A/commands", json!({"OpenAccount": {}}));
+
+        let conflict = || StoreError::Conflict {
+            stream: StreamId::new("BankAccount", "axum_3_A"),
+            sequence: 1,
+        };
+        let response = app(conflict).oneshot(open()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let problem: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 409);
+
+        let unavailable = || StoreError::Write(Box::new(std::io::Error::other("connection refused")));
+        let response = app(unavailable).oneshot(open()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let problem: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 503);
+
+        // 存在しないデータの読み込みは 404 になる
+        let not_found = StoreError::Read(Box::new(std::io::Error::from(std::io::ErrorKind::NotFound)));
+        assert_eq!(not_found.into_response().status(), StatusCode::NOT_FOUND);
+        let not_found = StoreError::Write(Box::new(std::io::Error::from(std::io::ErrorKind::NotFound)));
+        assert_eq!(not_found.into_response().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // 不正なコマンドは書き込む前に拒否される
+        let response = app(unavailable)
+            .oneshot(post("/BankAccount/axum_3_A/commands", json!({"Unknown": {}})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_extractors_and_problem_response() {
+        use crate::tests::{BankAccountCommand, WithdrawFunds};
+
+        async fn withdraw(
+            tsuzuri: TsuzuriHandle<crate::NoQueryStore>,
+            Path(id): Path<String>,
+            Metadata(metadata): Metadata,
+        ) -> Result<StatusCode, crate::ExecuteError<BankAccount>> {
+            let cmd = BankAccountCommand::WithdrawFunds(WithdrawFunds { amount: 10 });
+            tsuzuri.execute_with_metadata::<BankAccount>(&id, cmd, metadata).await?;
+            Ok(StatusCode::NO_CONTENT)
+        }
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let app = Router::new()
+            .route("/accounts/{id}/withdraw", ::axum::routing::post(withdraw))
+            .with_state(Arc::new(tsuzuri));
+
+        let request = Request::post("/accounts/axum_2_A/withdraw")
+            .header("x-correlation-id", "c-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let problem: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["status"], 422);
+        assert_eq!(problem["rejection"], "AccountNotOpen");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-correlation-id", HeaderValue::from_static("c-1"));
+        headers.insert("x-metadata-actor", HeaderValue::from_static("alice"));
+        headers.insert("accept", HeaderValue::from_static("*/*"));
+        headers.insert("x-metadata-channel", HeaderValue::from_static("web"));
+        let Metadata(metadata) = Metadata::from(&headers);
+        assert_eq!(metadata.correlation_id.as_deref(), Some("c-1"));
+        assert_eq!(metadata.actor.as_deref(), Some("alice"));
+        assert_eq!(
+            metadata.extensions,
+            HashMap::from([("channel".to_string(), "web".to_string())])
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Source of the current time.
+//!
+//! Every timestamp stamped by the library, such as [`Payload::created_at`],
+//! comes from the [`Clock`] configured with [`TsuzuriBuilder::clock`]. The
+//! [`SystemClock`] is used by default, and a [`ManualClock`] makes time
+//! deterministic in tests and replays.
+//!
+//! While a command is handled, the configured clock is also available to the
+//! aggregate through [`now`]:
+//!
+//! ```ignore
+//! impl Handle<OpenAccount> for BankAccount {
+//!     fn handle(&self, _cmd: OpenAccount) -> Result<Vec<BankAccountEvent>, Self::Error> {
+//!         Ok(vec![AccountOpened { opened_at: tsuzuri::clock::now() }.into()])
+//!     }
+//! }
+//! ```
+//!
+//! [`Payload::created_at`]: crate::store::payload::Payload::created_at
+//! [`TsuzuriBuilder::clock`]: crate::TsuzuriBuilder::clock
+
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use time::OffsetDateTime;
+
+/// Returns the current time.
+pub trait Clock: Debug + Send + Sync + 'static {
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// The system time in UTC.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Clones share the same time, so a clone kept by the test controls the clock
+/// given to the [`TsuzuriBuilder`](crate::TsuzuriBuilder).
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<OffsetDateTime>>,
+}
+
+impl ManualClock {
+    /// Creates a clock frozen at `now`.
+    pub fn new(now: OffsetDateTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, now: OffsetDateTime) {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner()) = now;
+    }
+
+    /// Moves the current time forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner()) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> OffsetDateTime {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Returns the time of the clock set by [`scope`], or the system time outside of it.
+pub fn now() -> OffsetDateTime {
+    CURRENT
+        .with(|current| current.borrow().as_ref().map(|clock| clock.now()))
+        .unwrap_or_else(OffsetDateTime::now_utc)
+}
+
+/// Runs `f` with `clock` as the clock returned by [`now`] on this thread.
+///
+/// The library sets it while handling a command. Tests can use it to handle
+/// commands directly against an aggregate at a chosen time.
+pub fn scope<R>(clock: &Arc<dyn Clock>, f: impl FnOnce() -> R) -> R {
+    // パニックした場合も元の時計に戻す
+    struct Restore(Option<Arc<dyn Clock>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(CURRENT.with(|current| current.borrow_mut().replace(Arc::clone(clock))));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_scope() {
+        let at = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let manual = ManualClock::new(at);
+        let clock: Arc<dyn Clock> = Arc::new(manual.clone());
+
+        assert_eq!(scope(&clock, now), at);
+        manual.advance(Duration::from_secs(60));
+        assert_eq!(clock.now(), at + Duration::from_secs(60));
+
+        let other: Arc<dyn Clock> = Arc::new(ManualClock::new(at));
+        scope(&clock, || {
+            assert_eq!(scope(&other, now), at);
+            assert_eq!(now(), at + Duration::from_secs(60));
+        });
+        assert_ne!(now(), at);
+    }
+}
@@ -0,0 +1,118 @@
+//! Converts events between their serde representation and the stored one.
+//!
+//! Serde externally tags enums with the variant identifier, or its `#[serde(rename)]`
+//! (`{"DepositedFunds": {...}}`, or `"Reset"` for unit variants). The stored
+//! representation uses the name from [`EventType`] instead, so renaming a variant
+//! does not break history.
+
+use crate::aggregate::EventType;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Serializes the event keyed by its [`EventType::event_type`].
+pub(crate) fn encode_event<E: Serialize + EventType>(event: &E) -> serde_json::Result<Vec<u8>> {
+    let value = rename_variant(serde_json::to_value(event)?, |_| Some(event.event_type()));
+    serde_json::to_vec(&value)
+}
+
+/// Deserializes an event keyed by its [`EventType::event_type`].
+pub(crate) fn decode_event<E: DeserializeOwned + EventType>(bytes: &[u8]) -> serde_json::Result<E> {
+    let value = rename_variant(serde_json::from_slice(bytes)?, E::variant_of);
+    serde_json::from_value(value)
+}
+
+/// Renames the tag of an externally tagged enum value.
+///
+/// Unknown names are kept as is, which lets events written under the variant
+/// identifier still be read.
+pub(crate) fn rename_variant(value: Value, rename: impl FnOnce(&str) -> Option<&'static str>) -> Value {
+    match value {
+        Value::String(name) => Value::String(rename(&name).map(str::to_string).unwrap_or(name)),
+        Value::Object(map) if map.len() == 1 => {
+            let (name, payload) = map.into_iter().next().expect("map has one entry");
+            let name = rename(&name).map(str::to_string).unwrap_or(name);
+            Value::Object([(name, payload)].into_iter().collect())
+        }
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::Event)]
+    enum AccountEvent {
+        #[event(name = "account_opened", version = 2)]
+        Opened {
+            owner: String,
+        },
+        #[event(name = "account_closed")]
+        Closed,
+        Frozen,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, crate::Event)]
+    #[serde(rename_all = "snake_case")]
+    enum CounterEvent {
+        Bumped {
+            by: u32,
+        },
+        #[event(name = "counter_reset")]
+        Reset,
+        #[serde(rename = "closed")]
+        CounterClosed,
+    }
+
+    #[test]
+    fn test_encode_decode_event() {
+        let event = AccountEvent::Opened { owner: "alice".into() };
+        assert_eq!(event.event_type(), "account_opened");
+        assert_eq!(event.event_version(), 2);
+        assert_eq!(AccountEvent::Frozen.event_type(), "Frozen");
+        assert_eq!(AccountEvent::Frozen.event_version(), 1);
+
+        let bytes = encode_event(&event).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&bytes).unwrap(),
+            serde_json::json!({ "account_opened": { "owner": "alice" } })
+        );
+        assert_eq!(decode_event::<AccountEvent>(&bytes).unwrap(), event);
+
+        let bytes = encode_event(&AccountEvent::Closed).unwrap();
+        assert_eq!(bytes, br#""account_closed""#);
+        assert_eq!(decode_event::<AccountEvent>(&bytes).unwrap(), AccountEvent::Closed);
+
+        // 変数名で書き込まれた過去のイベントも読める
+        assert_eq!(
+            decode_event::<AccountEvent>(br#""Closed""#).unwrap(),
+            AccountEvent::Closed
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_serde_renamed_event() {
+        let events = [
+            CounterEvent::Bumped { by: 2 },
+            CounterEvent::Reset,
+            CounterEvent::CounterClosed,
+        ];
+        let stored: Vec<_> = events
+            .iter()
+            .map(|event| serde_json::from_slice::<Value>(&encode_event(event).unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            stored,
+            [
+                serde_json::json!({ "Bumped": { "by": 2 } }),
+                serde_json::json!("counter_reset"),
+                serde_json::json!("CounterClosed"),
+            ]
+        );
+        for (event, value) in events.iter().zip(stored) {
+            let bytes = serde_json::to_vec(&value).unwrap();
+            assert_eq!(&decode_event::<CounterEvent>(&bytes).unwrap(), event);
+        }
+    }
+}
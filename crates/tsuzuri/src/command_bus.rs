@@ -0,0 +1,201 @@
+//! Routing of JSON commands to aggregates registered by name.
+//!
+//! Aggregates are registered on [`TsuzuriBuilder::register`](crate::TsuzuriBuilder::register)
+//! and commands are dispatched with [`Tsuzuri::dispatch`](crate::Tsuzuri::dispatch),
+//! so a single HTTP or queue consumer can serve every aggregate.
+
+use crate::{
+    aggregate::{Aggregate, Apply, CommandType, Handle, State},
+    clock::Clock,
+    error::AggregateError,
+    execute_command,
+    metadata::EventMetadata,
+    repository::Repository,
+    store::{stream::StreamId, sync::event_store::EventStore},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fmt::Debug, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Error returned when dispatching a command through the [`CommandBus`].
+#[derive(Debug, thiserror::Error)]
+pub enum CommandBusError {
+    #[error("Unknown aggregate type: {0}")]
+    UnknownAggregate(String),
+    #[error("Invalid command: {0}")]
+    InvalidCommand(#[source] serde_json::Error),
+    /// The command was handled. Rejections are reported as [`AggregateError::UserError`].
+    #[error(transparent)]
+    Aggregate(#[from] AggregateError<Value>),
+}
+
+/// Aggregates registered by name.
+#[derive(Clone, Default)]
+pub struct CommandBus {
+    handlers: HashMap<String, Arc<dyn DynHandler>>,
+}
+
+impl Debug for CommandBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandBus")
+            .field("aggregates", &self.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl CommandBus {
+    /// Registers the aggregate `T` under its [`Aggregate::aggregate_type`],
+    /// replacing any aggregate registered with the same name.
+    pub fn register<T>(&mut self)
+    where
+        T: Aggregate + 'static,
+        T::Command: DeserializeOwned + CommandType + Send,
+        State<T>: Apply<T::Event> + Handle<T::Command>,
+        <State<T> as Handle<T::Command>>::Error: Serialize + Send,
+    {
+        self.handlers
+            .insert(T::aggregate_type().to_string(), Arc::new(Handler::<T>(PhantomData)));
+    }
+
+    /// Returns the names of the registered aggregates.
+    pub fn aggregate_types(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, aggregate_type: &str) -> bool {
+        self.handlers.contains_key(aggregate_type)
+    }
+
+    /// Returns the stream of the aggregate registered under `name` with the given id.
+    pub fn stream_id(&self, name: &str, id: &str) -> Option<StreamId> {
+        Some(self.handlers.get(name)?.stream_id(id))
+    }
+
+    pub(crate) async fn dispatch(
+        &self,
+        event_store: &Arc<EventStore>,
+        clock: &Arc<dyn Clock>,
+        aggregate_type: &str,
+        id: &str,
+        cmd: Value,
+        metadata: EventMetadata,
+    ) -> Result<(), CommandBusError> {
+        let Some(handler) = self.handlers.get(aggregate_type) else {
+            return Err(CommandBusError::UnknownAggregate(aggregate_type.to_string()));
+        };
+        handler.dispatch(event_store, clock, id, cmd, metadata).await
+    }
+}
+
+trait DynHandler: Send + Sync {
+    fn stream_id(&self, id: &str) -> StreamId;
+
+    fn dispatch<'a>(
+        &'a self,
+        event_store: &'a Arc<EventStore>,
+        clock: &'a Arc<dyn Clock>,
+        id: &'a str,
+        cmd: Value,
+        metadata: EventMetadata,
+    ) -> BoxFuture<'a, Result<(), CommandBusError>>;
+}
+
+struct Handler<T>(PhantomData<fn() -> T>);
+
+impl<T> DynHandler for Handler<T>
+where
+    T: Aggregate + 'static,
+    T::Command: DeserializeOwned + CommandType + Send,
+    State<T>: Apply<T::Event> + Handle<T::Command>,
+    <State<T> as Handle<T::Command>>::Error: Serialize + Send,
+{
+    fn stream_id(&self, id: &str) -> StreamId {
+        StreamId::of::<T>(id)
+    }
+
+    fn dispatch<'a>(
+        &'a self,
+        event_store: &'a Arc<EventStore>,
+        clock: &'a Arc<dyn Clock>,
+        id: &'a str,
+        cmd: Value,
+        metadata: EventMetadata,
+    ) -> BoxFuture<'a, Result<(), CommandBusError>> {
+        Box::pin(async move {
+            let cmd = crate::codec::rename_variant(cmd, T::Command::variant_of);
+            let cmd = serde_json::from_value::<T::Command>(cmd).map_err(CommandBusError::InvalidCommand)?;
+            execute_command(
+                &Repository::<T>::new(Arc::clone(event_store), Arc::clone(clock)),
+                id,
+                cmd,
+                metadata,
+            )
+            .await
+            .map_err(|err| CommandBusError::Aggregate(into_json_error(err)))
+        })
+    }
+}
+
+fn into_json_error<E: Serialize + Debug>(err: AggregateError<E>) -> AggregateError<Value> {
+    match err {
+        AggregateError::UserError(err) => {
+            AggregateError::UserError(serde_json::to_value(&err).unwrap_or_else(|_| Value::String(format!("{err:?}"))))
+        }
+        AggregateError::Store(err) => AggregateError::Store(err),
+        AggregateError::Serialize(err) => AggregateError::Serialize(err),
+        AggregateError::Deserialize(err) => AggregateError::Deserialize(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::sync::memory_store::MemoryStore,
+        tests::{BankAccount, BankAccountError},
+        TsuzuriBuilder,
+    };
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_dispatch_json_commands() {
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new()))
+            .register::<BankAccount>()
+            .build();
+        let id = "bus_1_A";
+
+        tsuzuri
+            .dispatch("BankAccount", id, json!({"OpenAccount": {}}))
+            .await
+            .unwrap();
+        tsuzuri
+            .dispatch("BankAccount", id, json!({"DepositFunds": {"amount": 100}}))
+            .await
+            .unwrap();
+        tsuzuri
+            .dispatch("BankAccount", id, json!({"deposit_funds": {"amount": 50}}))
+            .await
+            .unwrap();
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<BankAccount>(id), 0)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let result = tsuzuri.dispatch("Customer", id, json!({"OpenAccount": {}})).await;
+        assert!(matches!(result, Err(CommandBusError::UnknownAggregate(name)) if name == "Customer"));
+
+        let result = tsuzuri.dispatch("BankAccount", id, json!({"CloseAccount": {}})).await;
+        assert!(matches!(result, Err(CommandBusError::InvalidCommand(_))));
+
+        let result = tsuzuri.dispatch("BankAccount", id, json!({"OpenAccount": {}})).await;
+        let expected = serde_json::to_value(BankAccountError::AccountAlreadyOpened).unwrap();
+        assert!(matches!(result, Err(CommandBusError::Aggregate(AggregateError::UserError(err))) if err == expected));
+    }
+}
@@ -0,0 +1,45 @@
+//! Events read back from the store.
+
+use crate::{
+    aggregate::EventType,
+    codec,
+    metadata::EventMetadata,
+    store::{
+        payload::{Payload, Uuid},
+        stream::StreamId,
+    },
+};
+use serde::de::DeserializeOwned;
+use time::OffsetDateTime;
+
+/// A stored event decoded into the aggregate's event type.
+#[derive(Clone, Debug)]
+pub struct EventEnvelope<E> {
+    /// Globally unique identifier of the event.
+    pub event_id: Uuid,
+    /// Stream the event belongs to.
+    pub stream: StreamId,
+    /// The sequence number of the event in its stream.
+    pub sequence: usize,
+    /// Time the event was stored.
+    pub created_at: OffsetDateTime,
+    pub metadata: EventMetadata,
+    pub event: E,
+}
+
+impl<E> EventEnvelope<E>
+where
+    E: DeserializeOwned + EventType,
+{
+    /// Decodes the event and metadata of the payload, as done when rehydrating an aggregate.
+    pub fn decode(payload: &Payload) -> serde_json::Result<Self> {
+        Ok(Self {
+            event_id: payload.event_id,
+            stream: payload.stream_id(),
+            sequence: payload.sequence,
+            created_at: payload.created_at,
+            metadata: payload.event_metadata()?,
+            event: codec::decode_event(&payload.bytes)?,
+        })
+    }
+}
@@ -1,13 +1,27 @@
-// use std::error;
+use crate::{
+    aggregate::{Aggregate, Handle, State},
+    store::sync::error::StoreError,
+};
+use std::fmt::Debug;
 
 #[derive(Debug)]
 pub enum Error {}
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-// /// The base error for the framework.
-// #[derive(Debug, thiserror::Error)]
-// pub enum AggregateError<T: error::Error> {
-//     #[error("{0}")]
-//     UserError(T),
-// }
+/// The base error for the framework.
+#[derive(Debug, thiserror::Error)]
+pub enum AggregateError<T: Debug> {
+    /// The command was rejected by the aggregate.
+    #[error("{0:?}")]
+    UserError(T),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("Failed to serialize event: {0}")]
+    Serialize(#[source] serde_json::Error),
+    #[error("Failed to deserialize event: {0}")]
+    Deserialize(#[source] serde_json::Error),
+}
+
+/// Error returned when executing a command against the aggregate `T`.
+pub type ExecuteError<T> = AggregateError<<State<T> as Handle<<T as Aggregate>::Command>>::Error>;
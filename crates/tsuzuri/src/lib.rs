@@ -8,30 +8,56 @@ mod macros;
 
 pub use tsuzuri_derive::*;
 
+mod codec;
 mod error;
+mod metrics;
+
+pub use envelope::EventEnvelope;
+pub use error::{AggregateError, ExecuteError};
+pub use metadata::EventMetadata;
 
 pub mod aggregate;
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod clock;
+pub mod command_bus;
+pub mod envelope;
+pub mod metadata;
+pub mod repository;
+pub mod saga;
 pub mod store;
+pub mod testing;
 
 use crate::{
-    aggregate::{Aggregate, Apply, Handle, State},
+    aggregate::{Aggregate, Apply, CommandType, Handle, State},
+    clock::{Clock, SystemClock},
+    command_bus::{CommandBus, CommandBusError},
+    repository::Repository,
     store::{
-        payload::Payload,
-        sync::{
-            error::StoreError, event_store::EventStore, query_store::QueryStore, reader::ReadStore, writer::WriteStore,
-        },
+        stream::StreamId,
+        sync::{event_store::EventStore, query_store::QueryStore, reader::ReadStore, writer::WriteStore},
     },
 };
-use std::{collections::HashMap, sync::Arc};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+use tracing::{field, Instrument, Span};
 
 pub struct Tsuzuri<Q> {
     event_store: Arc<EventStore>,
     query_store: Q,
+    command_bus: CommandBus,
+    clock: Arc<dyn Clock>,
 }
 
 pub struct TsuzuriBuilder<Q> {
     event_store: EventStore,
     query_store: Q,
+    command_bus: CommandBus,
+    clock: Arc<dyn Clock>,
 }
 
 impl TsuzuriBuilder<NoQueryStore> {
@@ -39,6 +65,8 @@ impl TsuzuriBuilder<NoQueryStore> {
         Self {
             event_store,
             query_store: NoQueryStore,
+            command_bus: CommandBus::default(),
+            clock: Arc::new(SystemClock),
         }
     }
 }
@@ -49,14 +77,36 @@ impl<Q> TsuzuriBuilder<Q> {
         TsuzuriBuilder {
             event_store: self.event_store,
             query_store: WithQueryStore(Arc::new(query)),
+            command_bus: self.command_bus,
+            clock: self.clock,
         }
     }
 
+    /// 保存するイベントの日時やハンドラの [`clock::now`] に使う時計を設定する
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// 集約を [`Aggregate::aggregate_type`] の名前で登録し、JSON のコマンドを [`Tsuzuri::dispatch`] で実行できるようにする
+    pub fn register<T>(mut self) -> Self
+    where
+        T: Aggregate + 'static,
+        T::Command: DeserializeOwned + CommandType + Send,
+        State<T>: Apply<T::Event> + Handle<T::Command>,
+        <State<T> as Handle<T::Command>>::Error: Serialize + Send,
+    {
+        self.command_bus.register::<T>();
+        self
+    }
+
     /// 現在の状態で Tsuzuri を生成する
     pub fn build(self) -> Tsuzuri<Q> {
         Tsuzuri {
             event_store: Arc::new(self.event_store),
             query_store: self.query_store,
+            command_bus: self.command_bus,
+            clock: self.clock,
         }
     }
 }
@@ -81,9 +131,19 @@ impl<Q> Tsuzuri<Q> {
         Self {
             event_store: event_store.into(),
             query_store,
+            command_bus: CommandBus::default(),
+            clock: Arc::new(SystemClock),
         }
     }
 
+    pub fn command_bus(&self) -> &CommandBus {
+        &self.command_bus
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
     pub fn es_write(&self) -> WriteStore {
         self.event_store.write_store.clone()
     }
@@ -92,50 +152,149 @@ impl<Q> Tsuzuri<Q> {
         self.event_store.read_store.clone()
     }
 
-    pub async fn execute<T>(&self, id: &str, cmd: T::Command) -> Result<(), StoreError>
+    pub async fn execute<T>(&self, id: &str, cmd: T::Command) -> Result<(), ExecuteError<T>>
     where
         T: Aggregate,
         State<T>: Apply<T::Event> + Handle<T::Command>,
     {
-        self.execute_with_metadata(id, cmd, HashMap::new()).await
+        self.execute_with_metadata(id, cmd, EventMetadata::default()).await
     }
 
     pub async fn execute_with_metadata<T>(
         &self,
         id: &str,
         cmd: T::Command,
-        metadata: HashMap<String, String>,
-    ) -> Result<(), StoreError>
+        metadata: impl Into<EventMetadata>,
+    ) -> Result<(), ExecuteError<T>>
+    where
+        T: Aggregate,
+        State<T>: Apply<T::Event> + Handle<T::Command>,
+    {
+        execute_command(&self.repository::<T>(), id, cmd, metadata.into()).await
+    }
+
+    /// Returns the repository loading and saving aggregates of type `T`.
+    pub fn repository<T>(&self) -> Repository<T>
     where
         T: Aggregate,
         State<T>: Apply<T::Event> + Handle<T::Command>,
     {
+        Repository::new(Arc::clone(&self.event_store), Arc::clone(&self.clock))
+    }
+
+    /// Executes a JSON command such as `{"DepositFunds": {"amount": 100}}` against
+    /// the aggregate registered as `aggregate_type`.
+    pub async fn dispatch(&self, aggregate_type: &str, id: &str, cmd: Value) -> Result<(), CommandBusError> {
+        self.dispatch_with_metadata(aggregate_type, id, cmd, EventMetadata::default())
+            .await
+    }
+
+    pub async fn dispatch_with_metadata(
+        &self,
+        aggregate_type: &str,
+        id: &str,
+        cmd: Value,
+        metadata: impl Into<EventMetadata>,
+    ) -> Result<(), CommandBusError> {
+        self.command_bus
+            .dispatch(&self.event_store, &self.clock, aggregate_type, id, cmd, metadata.into())
+            .await
+    }
+
+    /// Reads the events of the aggregate whose sequence is in `range`, e.g. `..` for every
+    /// event or `3..` from the third one.
+    #[tracing::instrument(
+        skip(self, range),
+        fields(aggregate_type = T::aggregate_type(), events = field::Empty),
+        err
+    )]
+    pub async fn events<T>(
+        &self,
+        id: &str,
+        range: impl RangeBounds<usize>,
+    ) -> Result<Vec<EventEnvelope<T::Event>>, ExecuteError<T>>
+    where
+        T: Aggregate,
+        State<T>: Handle<T::Command>,
+    {
+        let stream = StreamId::of::<T>(id);
+        let from = match range.start_bound() {
+            Bound::Included(&from) => from,
+            Bound::Excluded(&from) => from.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let to = match range.end_bound() {
+            Bound::Included(&to) => Some(to.saturating_add(1)),
+            Bound::Excluded(&to) => Some(to),
+            Bound::Unbounded => None,
+        };
+        let read_store = &self.event_store.read_store;
+        let payloads = match to {
+            Some(to) => read_store.read_to(&stream, from, to).await?,
+            None => read_store.read_to_latest(&stream, from).await?,
+        };
+        Span::current().record("events", payloads.len());
+        payloads
+            .iter()
+            .map(EventEnvelope::decode)
+            .collect::<Result<_, _>>()
+            .map_err(AggregateError::Deserialize)
+    }
+}
+
+async fn execute_command<T>(
+    repository: &Repository<T>,
+    id: &str,
+    cmd: T::Command,
+    metadata: EventMetadata,
+) -> Result<(), ExecuteError<T>>
+where
+    T: Aggregate,
+    State<T>: Apply<T::Event> + Handle<T::Command>,
+{
+    let span = tracing::info_span!(
+        "execute",
+        aggregate_type = T::aggregate_type(),
+        id,
+        from_sequence = field::Empty,
+        to_sequence = field::Empty,
+        events = field::Empty,
+    );
+    let result = async move {
         // 集約を再生する
-        let mut current_sequence = 0;
-        let events = self.es_read().read_to_latest(id, current_sequence).await?;
-        let mut agg = State::<T>::init(id.to_string());
-        for envelope in events {
-            current_sequence = envelope.sequence;
-            let event = serde_json::from_slice::<T::Event>(&envelope.bytes).unwrap();
-            agg.apply(event);
-        }
+        let (agg, current_sequence) = repository.load(id).await?;
         // 再生した集約にコマンドを適用する
-        let events = agg.handle(cmd).unwrap();
+        let events = tracing::info_span!("handle", error = field::Empty, otel.status_code = field::Empty)
+            .in_scope(|| {
+                // 拒否は handle スパンのエラーとして記録する
+                clock::scope(repository.clock(), || agg.handle(cmd)).inspect_err(|err| {
+                    Span::current()
+                        .record("error", field::debug(err))
+                        .record("otel.status_code", "ERROR");
+                    tracing::error!(error = ?err, "command rejected");
+                })
+            })
+            .map_err(AggregateError::UserError)?;
         // イベントを書き込む
-        for event in events {
-            current_sequence += 1;
-            let payload = Payload::new(
-                id,
-                current_sequence,
-                serde_json::to_vec(&event).unwrap(),
-                Some(serde_json::to_vec(&metadata).unwrap()),
-            )
-            .unwrap();
-            self.es_write().write(id, payload).await?;
-        }
+        Span::current()
+            .record("from_sequence", current_sequence + 1)
+            .record("to_sequence", current_sequence + events.len())
+            .record("events", events.len());
+        repository
+            .save_with_metadata(id, events, current_sequence, metadata)
+            .await?;
         // クエリを同期的に更新する
         Ok(())
     }
+    .instrument(span.clone())
+    .await;
+    if let Err(err @ (AggregateError::Store(_) | AggregateError::Serialize(_) | AggregateError::Deserialize(_))) =
+        &result
+    {
+        span.in_scope(|| tracing::error!(error = %err, "command failed"));
+    }
+    metrics::command(T::aggregate_type(), &result);
+    result
 }
 
 #[doc(hidden)]
@@ -143,6 +302,20 @@ pub mod __macro_helpers {
     use serde_json::Value;
     pub use {serde_json, tracing, tracing_tunnel};
 
+    /// Implemented by `#[derive(Event)]`, checked by `#[derive(Aggregate)]`.
+    #[diagnostic::on_unimplemented(message = "`{Self}` must derive `tsuzuri::Event`")]
+    pub trait DerivedEvent {}
+
+    /// Implemented by `#[derive(Command)]`, checked by `#[derive(Aggregate)]`.
+    #[diagnostic::on_unimplemented(message = "`{Self}` must derive `tsuzuri::Command`")]
+    pub trait DerivedCommand {}
+
+    // edition 2015 のクレートでは `dyn ::std::error::Error` と書けないため
+    pub type DynError = dyn std::error::Error + 'static;
+
+    pub const fn assert_derived_event<E: DerivedEvent>() {}
+    pub const fn assert_derived_command<C: DerivedCommand>() {}
+
     /// Extracts the event name and payload from an event json value.
     /// `{"EventName": {"foo": 1}}` returns `("EventName", {"foo": 1})`.
     pub fn extract_event_name_payload(value: Value) -> Result<(String, Value), &'static str> {
@@ -163,12 +336,14 @@ pub mod __macro_helpers {
     }
 }
 
+#[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
     use crate::{
         aggregate::{Aggregate, Apply, Handle},
-        events, Command, Event,
+        store::{payload::Payload, sync::error::StoreError},
+        Command, Event,
     };
     #[allow(unused_imports)]
     use serde::{Deserialize, Serialize};
@@ -186,31 +361,27 @@ mod tests {
         InsufficientBalance,
     }
 
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, Aggregate)]
+    #[aggregate(command = BankAccountCommand, event = BankAccountEvent, name = "BankAccount")]
     pub struct BankAccount {
+        #[aggregate(id)]
+        account_number: String,
         opened: bool,
-        balance: i64,
-    }
-
-    impl Aggregate for BankAccount {
-        type Command = BankAccountCommand;
-        type Event = BankAccountEvent;
-
-        fn init(_id: String) -> Self {
-            BankAccount {
-                opened: false,
-                balance: 0,
-            }
-        }
+        pub(crate) balance: i64,
     }
 
-    #[derive(Deserialize, Command)]
+    #[derive(Clone, Debug, Deserialize, Command)]
+    #[command(error = BankAccountError)]
     pub enum BankAccountCommand {
         OpenAccount(OpenAccount),
+        #[command(name = "deposit_funds")]
         DepositFunds(DepositFunds),
         WithdrawFunds(WithdrawFunds),
+        CloseAccount {
+            reason: String,
+        },
     }
-    #[derive(Deserialize)]
+    #[derive(Clone, Debug, Deserialize)]
     pub struct OpenAccount {}
 
     impl Handle<OpenAccount> for BankAccount {
@@ -225,9 +396,9 @@ mod tests {
         }
     }
 
-    #[derive(Deserialize)]
+    #[derive(Clone, Debug, Deserialize)]
     pub struct DepositFunds {
-        amount: u32,
+        pub amount: u32,
     }
 
     impl Handle<DepositFunds> for BankAccount {
@@ -246,9 +417,9 @@ mod tests {
         }
     }
 
-    #[derive(Deserialize)]
+    #[derive(Clone, Debug, Deserialize)]
     pub struct WithdrawFunds {
-        amount: u32,
+        pub amount: u32,
     }
 
     impl Handle<WithdrawFunds> for BankAccount {
@@ -272,11 +443,31 @@ mod tests {
         }
     }
 
+    impl Handle<bank_account_command::CloseAccount> for BankAccount {
+        type Error = BankAccountError;
+
+        fn handle(&self, cmd: bank_account_command::CloseAccount) -> Result<Vec<BankAccountEvent>, Self::Error> {
+            if !self.opened {
+                return Err(BankAccountError::AccountNotOpen);
+            }
+            tracing::info!(reason = cmd.reason, "closing account");
+
+            events![bank_account_event::AccountClosed]
+        }
+    }
+
     #[derive(Clone, Debug, Event, Serialize, Deserialize)]
     pub enum BankAccountEvent {
         OpenedAccount(AccountOpened),
         DepositedFunds(FundsDeposited),
         WithdrewFunds(FundsWithdrawn),
+        AccountClosed,
+    }
+
+    impl Apply<bank_account_event::AccountClosed> for BankAccount {
+        fn apply(&mut self, _event: bank_account_event::AccountClosed) {
+            self.opened = false;
+        }
     }
 
     #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -317,7 +508,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_write_with_writer() -> Result<(), StoreError> {
+    async fn test_write_with_writer() -> Result<(), ExecuteError<BankAccount>> {
         use crate::store::sync::memory_store::MemoryStore;
 
         let query_store = QueryStore::new(MemoryStore::new());
@@ -343,8 +534,533 @@ mod tests {
         let cmd = BankAccountCommand::WithdrawFunds(WithdrawFunds { amount: 50 });
         tsuzuri.execute::<BankAccount>(id, cmd).await?;
 
-        assert_eq!(tsuzuri.es_read().read_to_latest(id, 0).await?.len(), 4);
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<BankAccount>(id), 0)
+                .await?
+                .len(),
+            4
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejected_command() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::store::sync::memory_store::MemoryStore;
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let id = "test_2_A";
+
+        let cmd = BankAccountCommand::WithdrawFunds(WithdrawFunds { amount: 50 });
+        let result = tsuzuri.execute::<BankAccount>(id, cmd).await;
+        let Err(AggregateError::UserError(err)) = result else {
+            panic!("expected a rejection: {result:?}");
+        };
+        assert!(matches!(
+            err,
+            BankAccountCommandError::WithdrawFunds(BankAccountError::AccountNotOpen)
+        ));
+        assert_eq!(err.to_string(), "account not open");
+        assert_eq!(serde_json::Value::from(err), serde_json::json!("AccountNotOpen"));
+        assert!(tsuzuri
+            .es_read()
+            .read_to_latest(&StreamId::of::<BankAccount>(id), 0)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_metadata() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::store::sync::memory_store::MemoryStore;
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let id = "test_3_A";
+
+        let metadata = EventMetadata::new()
+            .with_correlation_id("c-1")
+            .with_causation_id("m-1")
+            .with_actor("alice")
+            .with_extension("channel", "web");
+        let cmd = BankAccountCommand::OpenAccount(OpenAccount {});
+        tsuzuri
+            .execute_with_metadata::<BankAccount>(id, cmd, metadata.clone())
+            .await?;
+        let cmd = BankAccountCommand::CloseAccount { reason: "moved".into() };
+        tsuzuri
+            .execute_with_metadata::<BankAccount>(id, cmd, metadata.clone())
+            .await?;
+
+        // コマンドのメタデータが全てのイベントに引き継がれる
+        let payloads = tsuzuri
+            .es_read()
+            .read_to_latest(&StreamId::of::<BankAccount>(id), 0)
+            .await?;
+        let decoded = payloads
+            .iter()
+            .map(Payload::event_metadata)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(AggregateError::Deserialize)?;
+        let event_ids: Vec<_> = payloads
+            .iter()
+            .map(|payload| Some(payload.event_id.to_string()))
+            .collect();
+        assert_eq!(
+            decoded,
+            [
+                EventMetadata {
+                    event_id: event_ids[0].clone(),
+                    event_type: Some("OpenedAccount".into()),
+                    event_version: Some(1),
+                    ..metadata.clone()
+                },
+                EventMetadata {
+                    event_id: event_ids[1].clone(),
+                    event_type: Some("AccountClosed".into()),
+                    event_version: Some(1),
+                    ..metadata
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streams_namespaced_by_aggregate_type() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::store::sync::memory_store::MemoryStore;
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+
+        // 同じ id でも集約の型ごとに別のストリームに書き込まれる
+        let id = "test_5_A";
+        tsuzuri.execute::<BankAccount>(id, OpenAccount {}.into()).await?;
+        tsuzuri
+            .execute::<BankAccount>(id, DepositFunds { amount: 10 }.into())
+            .await?;
+        let cmd = Record {
+            amount: 100,
+            currency: Jpy,
+        };
+        tsuzuri.execute::<Ledger<Jpy>>(id, cmd.into()).await.unwrap();
+        tsuzuri
+            .execute::<BankAccount>("test_5_B", OpenAccount {}.into())
+            .await?;
+
+        let (account, sequence) = tsuzuri.repository::<BankAccount>().load(id).await?;
+        assert_eq!((account.0.balance, sequence), (10, 2));
+        let stream = StreamId::of::<Ledger<Jpy>>(id);
+        assert_eq!(stream, StreamId::new("Ledger", id));
+        assert_eq!(tsuzuri.es_read().read_to_latest(&stream, 0).await?.len(), 1);
+
+        let payloads = tsuzuri.es_read().read_category("BankAccount").await?;
+        let streams: Vec<_> = payloads.iter().map(|payload| payload.id.as_str()).collect();
+        assert_eq!(streams, ["test_5_A", "test_5_A", "test_5_B"]);
+        assert_eq!(tsuzuri.es_read().read_all().await?.len(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_manual_clock() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::{clock::ManualClock, store::sync::memory_store::MemoryStore};
+        use std::time::Duration;
+
+        let at = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let clock = ManualClock::new(at);
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new()))
+            .clock(clock.clone())
+            .build();
+        assert_eq!(tsuzuri.clock().now(), at);
+
+        let id = "test_6_A";
+        tsuzuri.execute::<BankAccount>(id, OpenAccount {}.into()).await?;
+        clock.advance(Duration::from_secs(60));
+        tsuzuri
+            .execute::<BankAccount>(id, DepositFunds { amount: 10 }.into())
+            .await?;
+
+        let payloads = tsuzuri
+            .es_read()
+            .read_to_latest(&StreamId::of::<BankAccount>(id), 0)
+            .await?;
+        let created_at: Vec<_> = payloads.iter().map(|payload| payload.created_at).collect();
+        assert_eq!(created_at, [at, at + Duration::from_secs(60)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_typed_events() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::store::sync::memory_store::MemoryStore;
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let id = "test_7_A";
+
+        let metadata = EventMetadata::new().with_actor("alice");
+        tsuzuri
+            .execute_with_metadata::<BankAccount>(id, OpenAccount {}.into(), metadata)
+            .await?;
+        for amount in [10, 20, 30] {
+            tsuzuri
+                .execute::<BankAccount>(id, DepositFunds { amount }.into())
+                .await?;
+        }
+
+        let events = tsuzuri.events::<BankAccount>(id, ..).await?;
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0].event, BankAccountEvent::OpenedAccount(_)));
+        assert_eq!(events[0].stream, StreamId::of::<BankAccount>(id));
+        assert_eq!(events[0].metadata.actor.as_deref(), Some("alice"));
+        assert_eq!(events[0].metadata.event_id, Some(events[0].event_id.to_string()));
+
+        let amounts = |events: Vec<EventEnvelope<BankAccountEvent>>| {
+            events
+                .into_iter()
+                .map(|envelope| match envelope.event {
+                    BankAccountEvent::DepositedFunds(FundsDeposited { amount }) => (envelope.sequence, amount),
+                    event => panic!("unexpected event: {event:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            amounts(tsuzuri.events::<BankAccount>(id, 2..=3).await?),
+            [(2, 10), (3, 20)]
+        );
+        assert_eq!(
+            amounts(tsuzuri.events::<BankAccount>(id, 3..).await?),
+            [(3, 20), (4, 30)]
+        );
+        assert!(tsuzuri.events::<BankAccount>("test_7_B", ..).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_repository_load_and_save() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::store::sync::memory_store::MemoryStore;
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let repository = tsuzuri.repository::<BankAccount>();
+        let id = "test_8_A";
+
+        let (account, version) = repository.load(id).await?;
+        assert_eq!((account.0.opened, version), (false, 0));
+        let events = account
+            .handle(OpenAccount {}.into())
+            .map_err(AggregateError::UserError)?;
+        assert_eq!(repository.save(id, events, version).await?, 1);
+        tsuzuri
+            .execute::<BankAccount>(id, DepositFunds { amount: 100 }.into())
+            .await?;
+
+        let (account, version) = repository.load(id).await?;
+        assert_eq!((account.0.balance, version), (100, 2));
+
+        // 読み込んだ後に別のコマンドが実行されると衝突する
+        tsuzuri
+            .execute::<BankAccount>(id, DepositFunds { amount: 10 }.into())
+            .await?;
+        let events = account
+            .handle(WithdrawFunds { amount: 50 }.into())
+            .map_err(AggregateError::UserError)?;
+        let result = repository.save(id, events, version).await;
+        assert!(matches!(
+            result,
+            Err(AggregateError::Store(StoreError::Conflict { sequence: 3, .. }))
+        ));
+        let (account, version) = repository.load(id).await?;
+        assert_eq!((account.0.balance, version), (110, 3));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_repository_temporal_load() -> Result<(), ExecuteError<BankAccount>> {
+        use crate::{clock::ManualClock, store::sync::memory_store::MemoryStore};
+        use std::time::Duration;
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        let at = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let clock = ManualClock::new(at);
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new()))
+            .clock(clock.clone())
+            .build();
+        let repository = tsuzuri.repository::<BankAccount>();
+        let id = "test_9_A";
+
+        tsuzuri.execute::<BankAccount>(id, OpenAccount {}.into()).await?;
+        for amount in [10, 20, 30] {
+            clock.advance(day);
+            tsuzuri
+                .execute::<BankAccount>(id, DepositFunds { amount }.into())
+                .await?;
+        }
+
+        let (account, version) = repository.load_at(id, 3).await?;
+        assert_eq!((account.0.balance, version), (30, 3));
+        let (account, version) = repository.load_at(id, 10).await?;
+        assert_eq!((account.0.balance, version), (60, 4));
+
+        let (account, version) = repository.load_as_of(id, at + day * 2).await?;
+        assert_eq!((account.0.balance, version), (30, 3));
+        let (account, version) = repository.load_as_of(id, at + day * 2 - Duration::from_secs(1)).await?;
+        assert_eq!((account.0.balance, version), (10, 2));
+        let (account, version) = repository.load_as_of(id, at - day).await?;
+        assert_eq!((account.0.opened, version), (false, 0));
+
+        Ok(())
+    }
+
+    pub trait Currency:
+        Clone + std::fmt::Debug + Default + Send + Sync + Serialize + serde::de::DeserializeOwned + 'static
+    {
+        const CODE: &'static str;
+    }
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    pub struct Jpy;
+
+    impl Currency for Jpy {
+        const CODE: &'static str = "JPY";
+    }
+
+    #[derive(Debug, Default, Aggregate)]
+    #[aggregate(command = LedgerCommand<C>, event = LedgerEvent<C>, name = "Ledger")]
+    pub struct Ledger<C: Currency> {
+        total: u64,
+        currency: C,
+    }
+
+    #[derive(Clone, Debug, Deserialize, Command)]
+    #[serde(bound = "")]
+    #[command(error = String)]
+    pub enum LedgerCommand<C: Currency> {
+        Record(Record<C>),
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(bound = "C: Currency")]
+    pub struct Record<C> {
+        amount: u64,
+        currency: C,
+    }
+
+    impl<C: Currency> Handle<Record<C>> for Ledger<C> {
+        type Error = String;
+
+        fn handle(&self, cmd: Record<C>) -> Result<Vec<LedgerEvent<C>>, Self::Error> {
+            if cmd.amount == 0 {
+                return Err(format!("cannot record 0 {}", C::CODE));
+            }
+            events![ledger_event::Recorded {
+                amount: cmd.amount,
+                currency: cmd.currency,
+            }]
+        }
+    }
+
+    #[derive(Clone, Debug, Event, Serialize, Deserialize)]
+    #[serde(bound = "")]
+    pub enum LedgerEvent<C: Currency> {
+        Recorded { amount: u64, currency: C },
+        Cleared,
+    }
+
+    impl<C: Currency> Apply<ledger_event::Recorded<C>> for Ledger<C> {
+        fn apply(&mut self, event: ledger_event::Recorded<C>) {
+            self.total += event.amount;
+            self.currency = event.currency;
+        }
+    }
+
+    impl<C: Currency> Apply<ledger_event::Cleared> for Ledger<C> {
+        fn apply(&mut self, _event: ledger_event::Cleared) {
+            self.total = 0;
+        }
+    }
+
+    // ライフタイムを持つコマンド
+    #[derive(Command)]
+    pub enum LedgerNote<'a> {
+        #[command(error = String)]
+        Annotate(Annotate<'a>),
+        #[command(error = std::num::ParseIntError)]
+        Scale { factor: &'a str },
+    }
+
+    pub struct Annotate<'a> {
+        note: &'a str,
+    }
+
+    impl<'a, C: Currency> Handle<Annotate<'a>> for Ledger<C> {
+        type Error = String;
+
+        fn handle(&self, cmd: Annotate<'a>) -> Result<Vec<LedgerEvent<C>>, Self::Error> {
+            match cmd.note {
+                "clear" => events![ledger_event::Cleared],
+                note => Err(format!("unknown note: {note}")),
+            }
+        }
+    }
+
+    impl<'a, C: Currency> Handle<ledger_note::Scale<'a>> for Ledger<C> {
+        type Error = std::num::ParseIntError;
+
+        fn handle(&self, cmd: ledger_note::Scale<'a>) -> Result<Vec<LedgerEvent<C>>, Self::Error> {
+            let factor: u64 = cmd.factor.parse()?;
+            events![ledger_event::Recorded {
+                amount: self.total * factor.saturating_sub(1),
+                currency: self.currency.clone(),
+            }]
+        }
+    }
+
+    // 書き方の違う同じエラー型と、型パラメータのエラー型には変換を生成しない
+    #[derive(Command)]
+    pub enum LedgerCheck<'a, E: std::fmt::Debug> {
+        #[command(error = String)]
+        Annotate(Annotate<'a>),
+        #[command(error = std::string::String)]
+        Verify { note: &'a str },
+        #[command(error = E)]
+        Fail(Fail<E>),
+    }
+
+    #[derive(Command)]
+    #[command(error = E)]
+    pub enum LedgerFail<E: std::fmt::Debug> {
+        Fail(Fail<E>),
+    }
+
+    impl<'a, C: Currency> Handle<ledger_check::Verify<'a>> for Ledger<C> {
+        type Error = std::string::String;
+
+        fn handle(&self, cmd: ledger_check::Verify<'a>) -> Result<Vec<LedgerEvent<C>>, Self::Error> {
+            Err(format!("unverified note: {}", cmd.note))
+        }
+    }
+
+    pub struct Fail<E>(E);
+
+    impl<E: std::fmt::Debug, C: Currency> Handle<Fail<E>> for Ledger<C> {
+        type Error = E;
+
+        fn handle(&self, cmd: Fail<E>) -> Result<Vec<LedgerEvent<C>>, Self::Error> {
+            Err(cmd.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generic_aggregate() -> Result<(), ExecuteError<Ledger<Jpy>>> {
+        use crate::store::sync::memory_store::MemoryStore;
+
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let id = "test_4_A";
+
+        let cmd = Record {
+            amount: 100,
+            currency: Jpy,
+        };
+        tsuzuri.execute::<Ledger<Jpy>>(id, cmd.into()).await?;
+        let (ledger, sequence) = tsuzuri.repository::<Ledger<Jpy>>().load(id).await?;
+        assert_eq!((ledger.0.total, sequence), (100, 1));
+
+        let result = tsuzuri
+            .execute::<Ledger<Jpy>>(
+                id,
+                LedgerCommand::Record(Record {
+                    amount: 0,
+                    currency: Jpy,
+                }),
+            )
+            .await;
+        assert!(
+            matches!(result, Err(AggregateError::UserError(LedgerCommandError::Record(err))) if err == "cannot record 0 JPY")
+        );
+        // 全コマンドが同じエラー型なので、そのエラー型に変換できる
+        let err = LedgerCommandError::Record("rejected".to_string());
+        assert_eq!(String::from(err), "rejected");
+
+        let mut state = ledger;
+        let events = state.handle(LedgerNote::from(Annotate { note: "clear" })).unwrap();
+        state.apply(events[0].clone());
+        assert_eq!(state.0.total, 0);
+        assert_eq!(Ledger::<Jpy>::aggregate_type(), "Ledger");
+
+        // エラー型が異なるコマンドは、それぞれのエラーから変換できる
+        let err = state.handle(LedgerNote::Scale { factor: "x" }).unwrap_err();
+        assert!(matches!(err, LedgerNoteError::Scale(_)));
+        assert!(matches!(
+            LedgerNoteError::from("unknown".to_string()),
+            LedgerNoteError::Annotate(_)
+        ));
+
+        let err = state.handle(LedgerFail::from(Fail(7))).unwrap_err();
+        assert!(matches!(err, LedgerFailError::Fail(7)));
+        let err = state.handle(LedgerCheck::Fail(Fail(7))).unwrap_err();
+        assert!(matches!(err, LedgerCheckError::Fail(7)));
 
         Ok(())
     }
+
+    #[test]
+    fn test_derive_aggregate() {
+        assert_eq!(BankAccount::aggregate_type(), "BankAccount");
+        assert_eq!(BankAccount::init("test_3_A".to_string()).account_number, "test_3_A");
+        assert_eq!(Counter::aggregate_type(), "Counter");
+    }
+
+    #[derive(Debug, Default)]
+    pub struct Counter {
+        count: u32,
+    }
+
+    impl Aggregate for Counter {
+        type Command = ();
+        type Event = CounterEvent;
+
+        fn init(_id: String) -> Self {
+            Counter::default()
+        }
+
+        fn aggregate_type() -> &'static str {
+            "Counter"
+        }
+    }
+
+    #[derive(Clone, Debug, Event, Serialize, Deserialize)]
+    pub enum CounterEvent {
+        Incremented { by: u32 },
+        Reset,
+    }
+
+    impl Apply<counter_event::Incremented> for Counter {
+        fn apply(&mut self, event: counter_event::Incremented) {
+            self.count += event.by;
+        }
+    }
+
+    impl Apply<counter_event::Reset> for Counter {
+        fn apply(&mut self, _event: counter_event::Reset) {
+            self.count = 0;
+        }
+    }
+
+    #[test]
+    fn test_event_struct_and_unit_variants() {
+        let mut state = aggregate::State(Counter::default());
+        state.apply(CounterEvent::Incremented { by: 2 });
+        state.apply(CounterEvent::from(counter_event::Incremented { by: 3 }));
+        assert_eq!(state.0.count, 5);
+
+        state.apply(CounterEvent::from(counter_event::Reset));
+        assert_eq!(state.0.count, 0);
+
+        let json = serde_json::to_value(CounterEvent::Incremented { by: 1 }).unwrap();
+        assert_eq!(json, serde_json::json!({ "Incremented": { "by": 1 } }));
+    }
 }
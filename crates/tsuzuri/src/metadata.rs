@@ -0,0 +1,196 @@
+//! Metadata stored alongside every event.
+//!
+//! The metadata given to [`execute_with_metadata`](crate::Tsuzuri::execute_with_metadata)
+//! is copied to every event emitted by the command, together with the event
+//! type and version. It is stored as JSON in [`Payload::metadata`] and can be
+//! decoded back with [`Payload::event_metadata`].
+
+use crate::store::payload::Payload;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+
+/// Typed metadata of an event.
+///
+/// The standard fields are optional. Anything else is kept in `extensions`,
+/// which is flattened into the stored JSON, so metadata written as a plain
+/// string map is still readable.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventMetadata {
+    /// Identifier of the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    /// Name the event is stored under, see [`EventType`](crate::aggregate::EventType).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    /// Schema version of the event.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_version"
+    )]
+    pub event_version: Option<u32>,
+    /// Identifier shared by every message of the same business transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// Identifier of the message which caused this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub causation_id: Option<String>,
+    /// User or system on whose behalf the command was executed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// Tenant the aggregate belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// W3C trace context `traceparent`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+    /// W3C trace context `tracestate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracestate: Option<String>,
+    /// User defined entries.
+    #[serde(flatten)]
+    pub extensions: HashMap<String, String>,
+}
+
+impl EventMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns metadata for a command caused by the message with this metadata.
+    ///
+    /// The correlation id, actor, tenant and trace context are kept, and the
+    /// causation id points to this message.
+    pub fn caused_by(&self) -> Self {
+        let causation_id = self.event_id.clone().or_else(|| self.causation_id.clone());
+        Self {
+            correlation_id: self.correlation_id.clone().or_else(|| causation_id.clone()),
+            causation_id,
+            actor: self.actor.clone(),
+            tenant: self.tenant.clone(),
+            traceparent: self.traceparent.clone(),
+            tracestate: self.tracestate.clone(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    pub fn with_causation_id(mut self, causation_id: impl Into<String>) -> Self {
+        self.causation_id = Some(causation_id.into());
+        self
+    }
+
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    pub fn with_extension(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extensions.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Keys matching a standard field are moved to it, the others become extensions.
+impl From<HashMap<String, String>> for EventMetadata {
+    fn from(map: HashMap<String, String>) -> Self {
+        let value = serde_json::to_value(&map).expect("string map serializes");
+        serde_json::from_value(value).unwrap_or_else(|_| Self {
+            extensions: map,
+            ..Self::default()
+        })
+    }
+}
+
+// 以前は event_version を文字列で保存していたため、数値と文字列の両方を受け付ける
+fn deserialize_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Version {
+        Number(u32),
+        String(String),
+    }
+
+    match Option::<Version>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Version::Number(version)) => Ok(Some(version)),
+        Some(Version::String(version)) => version.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+impl Payload {
+    /// Decodes the metadata of the event. Payloads without metadata return the default.
+    pub fn event_metadata(&self) -> serde_json::Result<EventMetadata> {
+        match &self.metadata {
+            Some(bytes) => serde_json::from_slice(bytes),
+            None => Ok(EventMetadata::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::stream::StreamId;
+
+    #[test]
+    fn test_decode_metadata() {
+        let metadata = EventMetadata::new()
+            .with_correlation_id("c-1")
+            .with_actor("alice")
+            .with_extension("ip", "127.0.0.1");
+        let bytes = serde_json::to_vec(&metadata).unwrap();
+        let stream = StreamId::new("Metadata", "metadata_1");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(),
+            serde_json::json!({ "correlation_id": "c-1", "actor": "alice", "ip": "127.0.0.1" })
+        );
+        let payload = Payload::new(&stream, 1, b"{}".to_vec(), Some(bytes)).unwrap();
+        assert_eq!(payload.event_metadata().unwrap(), metadata);
+
+        // 文字列のマップとして書き込まれたメタデータも読める
+        let payload = Payload::new(
+            &stream,
+            2,
+            b"{}".to_vec(),
+            Some(br#"{"event_type": "AccountOpened", "event_version": "1", "x-request-id": "r-1"}"#.to_vec()),
+        )
+        .unwrap();
+        let metadata = payload.event_metadata().unwrap();
+        assert_eq!(metadata.event_type.as_deref(), Some("AccountOpened"));
+        assert_eq!(metadata.event_version, Some(1));
+        assert_eq!(metadata.extensions["x-request-id"], "r-1");
+
+        let payload = Payload::new(&stream, 3, b"{}".to_vec(), None).unwrap();
+        assert_eq!(payload.event_metadata().unwrap(), EventMetadata::default());
+    }
+
+    #[test]
+    fn test_caused_by() {
+        let map = HashMap::from([
+            ("correlation_id".to_string(), "c-1".to_string()),
+            ("tenant".to_string(), "acme".to_string()),
+            ("ip".to_string(), "127.0.0.1".to_string()),
+        ]);
+        let metadata = EventMetadata {
+            event_id: Some("e-1".into()),
+            ..EventMetadata::from(map)
+        };
+        assert_eq!(metadata.tenant.as_deref(), Some("acme"));
+
+        let caused = metadata.caused_by();
+        assert_eq!(caused.correlation_id.as_deref(), Some("c-1"));
+        assert_eq!(caused.causation_id.as_deref(), Some("e-1"));
+        assert_eq!(caused.tenant.as_deref(), Some("acme"));
+        assert!(caused.extensions.is_empty());
+    }
+}
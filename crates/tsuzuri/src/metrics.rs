@@ -0,0 +1,178 @@
+//! Metrics emitted through the `metrics` facade when the `metrics` feature is enabled.
+//!
+//! Without the feature every function here is a no-op, so call sites don't need
+//! their own `cfg` attributes.
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+
+use crate::{error::AggregateError, store::sync::error::StoreError};
+use std::{fmt::Debug, time::Duration};
+
+/// Counts an executed command by aggregate and outcome, and the conflicts among them.
+pub(crate) fn command<E: Debug>(aggregate: &'static str, result: &Result<(), AggregateError<E>>) {
+    let outcome = match result {
+        Ok(()) => "accepted",
+        Err(AggregateError::UserError(_)) => "rejected",
+        Err(AggregateError::Store(StoreError::Conflict { .. })) => "conflict",
+        Err(_) => "error",
+    };
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!("tsuzuri_commands_total", "aggregate" => aggregate, "outcome" => outcome).increment(1);
+        if outcome == "conflict" {
+            metrics::counter!("tsuzuri_concurrency_conflicts_total", "aggregate" => aggregate).increment(1);
+        }
+    }
+}
+
+pub(crate) fn events_appended(aggregate: &'static str, count: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("tsuzuri_events_appended_total", "aggregate" => aggregate).increment(count as u64);
+}
+
+pub(crate) fn rehydration(aggregate: &'static str, events: usize, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::histogram!("tsuzuri_rehydration_events", "aggregate" => aggregate).record(events as f64);
+        metrics::histogram!("tsuzuri_rehydration_duration_seconds", "aggregate" => aggregate).record(elapsed);
+    }
+}
+
+/// Records the latency of an operation of the event store or the query store, labeled `store`.
+pub(crate) fn store_operation<T>(
+    store: &'static str,
+    operation: &'static str,
+    result: &Result<T, StoreError>,
+    elapsed: Duration,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        metrics::histogram!(
+            "tsuzuri_store_operation_duration_seconds",
+            "store" => store,
+            "operation" => operation,
+            "outcome" => outcome
+        )
+        .record(elapsed);
+    }
+}
+
+pub(crate) fn cache(hit: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let result = if hit { "hit" } else { "miss" };
+        metrics::counter!("tsuzuri_cache_requests_total", "result" => result).increment(1);
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::{
+        store::{
+            payload::Payload,
+            stream::StreamId,
+            sync::{
+                event_store::EventStore,
+                layer::{CacheLayer, Layer},
+                memory_store::MemoryStore,
+                query_store::QueryStore,
+                reader::Reader,
+                writer::Writer,
+            },
+        },
+        tests::{BankAccount, BankAccountCommand, OpenAccount, WithdrawFunds},
+        TsuzuriBuilder,
+    };
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        CompositeKey,
+    };
+
+    type Snapshot = Vec<(
+        CompositeKey,
+        Option<metrics::Unit>,
+        Option<metrics::SharedString>,
+        DebugValue,
+    )>;
+
+    fn value<'a>(snapshot: &'a Snapshot, name: &str, labels: &[(&str, &str)]) -> Option<&'a DebugValue> {
+        snapshot
+            .iter()
+            .find(|(key, ..)| {
+                let key = key.key();
+                key.name() == name
+                    && labels
+                        .iter()
+                        .all(|(label, value)| key.labels().any(|l| l.key() == *label && l.value() == *value))
+            })
+            .map(|(.., value)| value)
+    }
+
+    #[test]
+    fn test_recorded_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        // ローカルのレコーダーはスレッドごとなので、同じスレッドで実行する
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+                let id = "metrics_1_A";
+                tsuzuri
+                    .execute::<BankAccount>(id, BankAccountCommand::OpenAccount(OpenAccount {}))
+                    .await
+                    .unwrap();
+                let cmd = BankAccountCommand::WithdrawFunds(WithdrawFunds { amount: 10 });
+                tsuzuri.execute::<BankAccount>(id, cmd).await.unwrap_err();
+
+                let store = MemoryStore::new();
+                let stream = StreamId::new("Metrics", "metrics_1_B");
+                store
+                    .write(&stream, Payload::new(&stream, 1, vec![], None).unwrap())
+                    .await
+                    .unwrap();
+                let cached = CacheLayer::new(16).layer(store);
+                cached.read(&stream, 1).await.unwrap();
+                cached.read(&stream, 1).await.unwrap();
+
+                let query_store = QueryStore::new(MemoryStore::new());
+                query_store.read_store.read_to_latest(&stream, 0).await.unwrap();
+            })
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let counter = |name, labels: &[(&str, &str)]| match value(&snapshot, name, labels) {
+            Some(DebugValue::Counter(count)) => *count,
+            other => panic!("{name} {labels:?}: {other:?}"),
+        };
+        let commands = "tsuzuri_commands_total";
+        assert_eq!(
+            counter(commands, &[("aggregate", "BankAccount"), ("outcome", "accepted")]),
+            1
+        );
+        assert_eq!(
+            counter(commands, &[("aggregate", "BankAccount"), ("outcome", "rejected")]),
+            1
+        );
+        assert_eq!(
+            counter("tsuzuri_events_appended_total", &[("aggregate", "BankAccount")]),
+            1
+        );
+        assert_eq!(counter("tsuzuri_cache_requests_total", &[("result", "miss")]), 1);
+        assert_eq!(counter("tsuzuri_cache_requests_total", &[("result", "hit")]), 1);
+
+        let latency = "tsuzuri_store_operation_duration_seconds";
+        assert!(value(&snapshot, latency, &[("store", "event"), ("operation", "append")]).is_some());
+        assert!(value(
+            &snapshot,
+            latency,
+            &[("store", "query"), ("operation", "read_to_latest")]
+        )
+        .is_some());
+        assert!(value(&snapshot, latency, &[("store", "query"), ("operation", "append")]).is_none());
+    }
+}
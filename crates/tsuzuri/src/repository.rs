@@ -0,0 +1,212 @@
+//! Loading and saving aggregates.
+//!
+//! [`Tsuzuri::execute`](crate::Tsuzuri::execute) loads the aggregate, handles
+//! the command and saves the emitted events through a [`Repository`]. The
+//! same steps are available for workflows that need the current state before
+//! deciding what to do:
+//!
+//! ```ignore
+//! let repository = tsuzuri.repository::<BankAccount>();
+//! let (account, version) = repository.load(id).await?;
+//! if account.0.balance >= amount {
+//!     let events = account.handle(WithdrawFunds { amount }.into()).map_err(AggregateError::UserError)?;
+//!     repository.save(id, events, version).await?;
+//! }
+//! ```
+//!
+//! Past states are rebuilt with [`Repository::load_at`] for a version and
+//! [`Repository::load_as_of`] for a point in time, replaying the stream from
+//! its first event.
+
+use crate::{
+    aggregate::{Aggregate, Apply, EventType, Handle, State},
+    clock::{self, Clock},
+    codec,
+    error::{AggregateError, ExecuteError},
+    metadata::EventMetadata,
+    metrics,
+    store::{
+        page::ReadOptions,
+        payload::Payload,
+        stream::StreamId,
+        sync::{error::StoreError, event_store::EventStore},
+    },
+};
+use futures_util::{future, stream, Stream, TryStreamExt};
+use std::{fmt, marker::PhantomData, pin::pin, sync::Arc, time::Instant};
+use time::OffsetDateTime;
+use tracing::{field, Instrument, Span};
+
+/// Loads and saves aggregates of type `T` in the event store.
+pub struct Repository<T> {
+    event_store: Arc<EventStore>,
+    clock: Arc<dyn Clock>,
+    _aggregate: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Repository<T> {
+    fn clone(&self) -> Self {
+        Self {
+            event_store: Arc::clone(&self.event_store),
+            clock: Arc::clone(&self.clock),
+            _aggregate: PhantomData,
+        }
+    }
+}
+
+impl<T: Aggregate> fmt::Debug for Repository<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Repository")
+            .field("aggregate_type", &T::aggregate_type())
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Repository<T>
+where
+    T: Aggregate,
+    State<T>: Apply<T::Event> + Handle<T::Command>,
+{
+    pub fn new(event_store: Arc<EventStore>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            event_store,
+            clock,
+            _aggregate: PhantomData,
+        }
+    }
+
+    pub(crate) fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Replays every stored event of the aggregate, returning it with its version,
+    /// the sequence of its latest event. An aggregate without events has version 0.
+    #[tracing::instrument(
+        skip(self),
+        fields(aggregate_type = T::aggregate_type(), events = field::Empty, sequence = field::Empty)
+    )]
+    pub async fn load(&self, id: &str) -> Result<(State<T>, usize), ExecuteError<T>> {
+        let started = Instant::now();
+        let stream = StreamId::of::<T>(id);
+        // ページ単位で読み、長いストリームでも全件をメモリに載せない
+        let payloads = self.event_store.read_store.stream(&stream, ReadOptions::forward());
+        self.replay(id, payloads, started).await
+    }
+
+    /// Replays the events of the aggregate up to and including `version`.
+    ///
+    /// The returned version is the sequence of the last replayed event, which is
+    /// lower than `version` when the aggregate had fewer events.
+    #[tracing::instrument(
+        skip(self),
+        fields(aggregate_type = T::aggregate_type(), events = field::Empty, sequence = field::Empty)
+    )]
+    pub async fn load_at(&self, id: &str, version: usize) -> Result<(State<T>, usize), ExecuteError<T>> {
+        let started = Instant::now();
+        let payloads = self
+            .event_store
+            .read_store
+            .read_to(&StreamId::of::<T>(id), 0, version.saturating_add(1))
+            .await?;
+        self.replay(id, stream::iter(payloads.into_iter().map(Ok)), started)
+            .await
+    }
+
+    /// Replays the events of the aggregate stored at or before `at`, e.g. to get
+    /// the balance of an account at the end of a month.
+    #[tracing::instrument(
+        skip(self),
+        fields(aggregate_type = T::aggregate_type(), events = field::Empty, sequence = field::Empty)
+    )]
+    pub async fn load_as_of(&self, id: &str, at: OffsetDateTime) -> Result<(State<T>, usize), ExecuteError<T>> {
+        let started = Instant::now();
+        let stream = StreamId::of::<T>(id);
+        // 時刻はシーケンス順に増えるため、最初に `at` を超えたイベントで止める
+        let payloads = self
+            .event_store
+            .read_store
+            .stream(&stream, ReadOptions::forward())
+            .try_take_while(|payload| future::ready(Ok(payload.created_at <= at)));
+        self.replay(id, payloads, started).await
+    }
+
+    async fn replay(
+        &self,
+        id: &str,
+        payloads: impl Stream<Item = Result<Payload, StoreError>>,
+        started: Instant,
+    ) -> Result<(State<T>, usize), ExecuteError<T>> {
+        let mut agg = State::<T>::init(id.to_string());
+        let (mut replayed, mut current_sequence) = (0, 0);
+        let mut payloads = pin!(payloads);
+        while let Some(payload) = payloads.try_next().await? {
+            current_sequence = payload.sequence;
+            let event = codec::decode_event::<T::Event>(&payload.bytes).map_err(AggregateError::Deserialize)?;
+            agg.apply(event);
+            replayed += 1;
+        }
+        Span::current()
+            .record("events", replayed)
+            .record("sequence", current_sequence);
+        metrics::rehydration(T::aggregate_type(), replayed, started.elapsed());
+        Ok((agg, current_sequence))
+    }
+
+    /// Appends `events` after `expected_version`, returning the new version.
+    /// The events are those returned by [`Handle::handle`], i.e. `T::Event`.
+    ///
+    /// The events are appended atomically. Fails with [`StoreError::Conflict`], saving
+    /// none of them, when another event was saved since the aggregate was loaded.
+    pub async fn save(
+        &self,
+        id: &str,
+        events: Vec<<State<T> as Aggregate>::Event>,
+        expected_version: usize,
+    ) -> Result<usize, ExecuteError<T>> {
+        self.save_with_metadata(id, events, expected_version, EventMetadata::default())
+            .await
+    }
+
+    /// Like [`save`](Self::save), storing `metadata` with every event.
+    pub async fn save_with_metadata(
+        &self,
+        id: &str,
+        events: Vec<<State<T> as Aggregate>::Event>,
+        expected_version: usize,
+        metadata: EventMetadata,
+    ) -> Result<usize, ExecuteError<T>> {
+        let stream = StreamId::of::<T>(id);
+        let (from_sequence, to_sequence) = (expected_version + 1, expected_version + events.len());
+        let span = tracing::info_span!("write_events", %stream, from_sequence, to_sequence, events = events.len());
+        async {
+            let mut payloads = Vec::with_capacity(events.len());
+            for (sequence, event) in (from_sequence..).zip(events) {
+                let bytes = codec::encode_event(&event).map_err(AggregateError::Serialize)?;
+                let mut payload = clock::scope(&self.clock, || Payload::new(&stream, sequence, bytes, None))
+                    .map_err(|err| StoreError::Write(Box::new(err)))?;
+                let metadata = EventMetadata {
+                    event_id: Some(payload.event_id.to_string()),
+                    event_type: Some(event.event_type().to_string()),
+                    event_version: Some(event.event_version()),
+                    ..metadata.clone()
+                };
+                payload.metadata = Some(serde_json::to_vec(&metadata).map_err(AggregateError::Serialize)?);
+                payloads.push(payload);
+            }
+            if payloads.is_empty() {
+                return Ok(expected_version);
+            }
+            // 全イベントをまとめて追記し、競合した場合は何も保存しない
+            let appended = payloads.len();
+            self.event_store
+                .write_store
+                .append(&stream, expected_version, payloads)
+                .await?;
+            metrics::events_appended(T::aggregate_type(), appended);
+            Ok(to_sequence)
+        }
+        .instrument(span)
+        .await
+    }
+}
@@ -0,0 +1,511 @@
+//! Process managers coordinating several aggregates.
+//!
+//! A [`ProcessManager`] reacts to events by dispatching commands to other
+//! aggregates through [`Tsuzuri::execute`]. It is an aggregate itself: every
+//! input and every step outcome is recorded as an event in the event store,
+//! so a process can be resumed after a crash by replaying its stream.
+//!
+//! Steps are delivered at least once. A step is dispatched before its outcome
+//! is recorded, so a crash in between dispatches it again on resume. Each
+//! dispatch carries the process id as the correlation id and
+//! `{process id}/{step index}` as the causation id, where the step index is the
+//! version of the process stream when the step is dispatched. The index is the
+//! same when the step is dispatched again, so steps must be idempotent or the
+//! target must deduplicate commands by their causation id.
+
+use crate::{
+    aggregate::{Aggregate, Apply, Handle, State},
+    error::AggregateError,
+    metadata::EventMetadata,
+    Tsuzuri,
+};
+use async_trait::async_trait;
+use std::{error::Error, fmt::Debug};
+
+/// A long-running business process spanning several aggregates.
+///
+/// After each recorded input or step outcome, the first of
+/// [`pending_steps`](ProcessManager::pending_steps) is dispatched. Its outcome
+/// is fed back through [`on_completed`](ProcessManager::on_completed) or
+/// [`on_rejected`](ProcessManager::on_rejected), which must record events that
+/// remove the step from the pending ones. An outcome recording no events stops
+/// processing with [`ProcessError::StepStillPending`]. Compensating steps are
+/// scheduled by returning them from `pending_steps` once a rejection has been
+/// recorded.
+///
+/// Steps may be dispatched more than once, see the [module docs](self).
+pub trait ProcessManager: Aggregate {
+    /// The event consumed by the process manager.
+    type Input;
+    /// A command dispatched to another aggregate.
+    type Step;
+
+    /// Returns the id of the process instance the input belongs to, or `None` to ignore it.
+    fn correlation_id(input: &Self::Input) -> Option<String>;
+
+    /// Converts an input into the command handled by the process manager.
+    fn on_input(input: Self::Input) -> Self::Command;
+
+    /// Returns the steps still to be dispatched, in order.
+    fn pending_steps(&self) -> Vec<Self::Step>;
+
+    /// Returns the command recording that the step was accepted.
+    fn on_completed(step: Self::Step) -> Self::Command;
+
+    /// Returns the command recording that the step was rejected.
+    fn on_rejected(step: Self::Step, reason: String) -> Self::Command;
+}
+
+/// Dispatches a process manager step through [`Tsuzuri`].
+///
+/// The metadata identifies the step and should be passed to
+/// [`Tsuzuri::execute_with_metadata`], so the target can deduplicate a step
+/// dispatched again after a crash.
+#[async_trait]
+pub trait Dispatch<Q>: Send + Sync {
+    async fn dispatch(&self, tsuzuri: &Tsuzuri<Q>, metadata: EventMetadata) -> Result<(), StepError>;
+}
+
+/// The outcome of a step that was not accepted.
+#[derive(Debug, thiserror::Error)]
+pub enum StepError {
+    /// The target aggregate rejected the command. The process manager is asked to compensate.
+    #[error("Step rejected: {0}")]
+    Rejected(String),
+    /// The command could not be executed. The process stops and can be resumed later.
+    #[error("Step failed: {0}")]
+    Failed(#[source] Box<dyn Error + Send + Sync>),
+}
+
+impl<E: Debug> From<AggregateError<E>> for StepError {
+    fn from(err: AggregateError<E>) -> Self {
+        match err {
+            AggregateError::UserError(err) => StepError::Rejected(format!("{err:?}")),
+            AggregateError::Store(err) => StepError::Failed(Box::new(err)),
+            AggregateError::Serialize(err) | AggregateError::Deserialize(err) => StepError::Failed(Box::new(err)),
+        }
+    }
+}
+
+/// Error returned by [`Tsuzuri::process`].
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessError<E: Debug> {
+    #[error(transparent)]
+    Aggregate(#[from] AggregateError<E>),
+    #[error(transparent)]
+    Step(Box<dyn Error + Send + Sync>),
+    /// The outcome of a step recorded no events, so the step would be dispatched again.
+    #[error("Step of process {id} is still pending after its outcome was recorded")]
+    StepStillPending { id: String },
+}
+
+/// Error returned when processing an input with the process manager `P`.
+pub type ProcessExecuteError<P> = ProcessError<<State<P> as Handle<<P as Aggregate>::Command>>::Error>;
+
+impl<Q> Tsuzuri<Q>
+where
+    Q: Send + Sync,
+{
+    /// Records the input in the correlated process and dispatches its pending steps.
+    pub async fn process<P>(&self, input: P::Input) -> Result<(), ProcessExecuteError<P>>
+    where
+        P: ProcessManager,
+        P::Step: Dispatch<Q>,
+        State<P>: Apply<P::Event> + Handle<P::Command>,
+    {
+        let Some(id) = P::correlation_id(&input) else {
+            return Ok(());
+        };
+        self.execute::<P>(&id, P::on_input(input)).await?;
+        self.resume::<P>(&id).await
+    }
+
+    /// Dispatches the pending steps of a process until none are left.
+    pub async fn resume<P>(&self, id: &str) -> Result<(), ProcessExecuteError<P>>
+    where
+        P: ProcessManager,
+        P::Step: Dispatch<Q>,
+        State<P>: Apply<P::Event> + Handle<P::Command>,
+    {
+        let mut recorded = None;
+        loop {
+            let (state, index) = self.repository::<P>().load(id).await.map_err(ProcessError::Aggregate)?;
+            let Some(step) = state.0.pending_steps().into_iter().next() else {
+                return Ok(());
+            };
+            // 結果を記録してもストリームが進まない場合は、同じステップを送り続けないよう止める
+            if recorded == Some(index) {
+                return Err(ProcessError::StepStillPending { id: id.to_string() });
+            }
+            recorded = Some(index);
+            // 再送しても同じ ID になるよう、プロセスの ID とバージョンから作る
+            let metadata = EventMetadata::new()
+                .with_correlation_id(id)
+                .with_causation_id(format!("{id}/{index}"));
+            let cmd = match step.dispatch(self, metadata).await {
+                Ok(()) => P::on_completed(step),
+                Err(StepError::Rejected(reason)) => {
+                    tracing::warn!(id, %reason, "process step rejected");
+                    P::on_rejected(step, reason)
+                }
+                Err(StepError::Failed(err)) => return Err(ProcessError::Step(err)),
+            };
+            self.execute::<P>(id, cmd).await.map_err(ProcessError::Aggregate)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::{
+            stream::StreamId,
+            sync::{error::StoreError, event_store::EventStore, memory_store::MemoryStore},
+        },
+        tests::{BankAccount, BankAccountCommand, DepositFunds, OpenAccount, WithdrawFunds},
+        Command, Event, TsuzuriBuilder,
+    };
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Default)]
+    struct Transfer {
+        from: String,
+        to: String,
+        amount: u32,
+        completed: Vec<TransferStep>,
+        rejected: bool,
+    }
+
+    impl Aggregate for Transfer {
+        type Command = TransferCommand;
+        type Event = TransferEvent;
+
+        fn init(_id: String) -> Self {
+            Transfer::default()
+        }
+
+        fn aggregate_type() -> &'static str {
+            "Transfer"
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum TransferStep {
+        Withdraw { account: String, amount: u32 },
+        Deposit { account: String, amount: u32 },
+        Refund { account: String, amount: u32 },
+    }
+
+    #[async_trait]
+    impl<Q: Send + Sync> Dispatch<Q> for TransferStep {
+        async fn dispatch(&self, tsuzuri: &Tsuzuri<Q>, metadata: EventMetadata) -> Result<(), StepError> {
+            let (account, cmd) = match self {
+                TransferStep::Withdraw { account, amount } => (
+                    account,
+                    BankAccountCommand::WithdrawFunds(WithdrawFunds { amount: *amount }),
+                ),
+                TransferStep::Deposit { account, amount } | TransferStep::Refund { account, amount } => (
+                    account,
+                    BankAccountCommand::DepositFunds(DepositFunds { amount: *amount }),
+                ),
+            };
+            Ok(tsuzuri
+                .execute_with_metadata::<BankAccount>(account, cmd, metadata)
+                .await?)
+        }
+    }
+
+    struct TransferRequested {
+        transfer_id: String,
+        from: String,
+        to: String,
+        amount: u32,
+    }
+
+    #[derive(Command)]
+    #[command(handle_enum)]
+    enum TransferCommand {
+        Start { from: String, to: String, amount: u32 },
+        Complete { step: TransferStep },
+        Reject { step: TransferStep },
+    }
+
+    impl Handle<TransferCommand> for Transfer {
+        type Error = ();
+
+        fn handle(&self, cmd: TransferCommand) -> Result<Vec<TransferEvent>, Self::Error> {
+            match cmd {
+                TransferCommand::Start { from, to, amount } => events![TransferStarted { from, to, amount }],
+                TransferCommand::Complete { step } => events![StepCompleted { step }],
+                TransferCommand::Reject { step } => events![StepRejected { step }],
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Event, Serialize, Deserialize)]
+    enum TransferEvent {
+        Started(TransferStarted),
+        Completed(StepCompleted),
+        Rejected(StepRejected),
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct TransferStarted {
+        from: String,
+        to: String,
+        amount: u32,
+    }
+
+    impl Apply<TransferStarted> for Transfer {
+        fn apply(&mut self, event: TransferStarted) {
+            self.from = event.from;
+            self.to = event.to;
+            self.amount = event.amount;
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct StepCompleted {
+        step: TransferStep,
+    }
+
+    impl Apply<StepCompleted> for Transfer {
+        fn apply(&mut self, event: StepCompleted) {
+            self.completed.push(event.step);
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct StepRejected {
+        step: TransferStep,
+    }
+
+    impl Apply<StepRejected> for Transfer {
+        fn apply(&mut self, event: StepRejected) {
+            self.rejected = true;
+            self.completed.push(event.step);
+        }
+    }
+
+    impl ProcessManager for Transfer {
+        type Input = TransferRequested;
+        type Step = TransferStep;
+
+        fn correlation_id(input: &TransferRequested) -> Option<String> {
+            Some(input.transfer_id.clone())
+        }
+
+        fn on_input(input: TransferRequested) -> TransferCommand {
+            TransferCommand::Start {
+                from: input.from,
+                to: input.to,
+                amount: input.amount,
+            }
+        }
+
+        fn pending_steps(&self) -> Vec<TransferStep> {
+            let withdraw = TransferStep::Withdraw {
+                account: self.from.clone(),
+                amount: self.amount,
+            };
+            let deposit = TransferStep::Deposit {
+                account: self.to.clone(),
+                amount: self.amount,
+            };
+            let refund = TransferStep::Refund {
+                account: self.from.clone(),
+                amount: self.amount,
+            };
+            let steps = match (self.rejected, self.completed.contains(&withdraw)) {
+                (false, _) => vec![withdraw, deposit],
+                // 出金済みで入金が拒否された場合は、出金元へ返金する
+                (true, true) => vec![refund],
+                (true, false) => vec![],
+            };
+            steps
+                .into_iter()
+                .filter(|step| !self.completed.contains(step))
+                .collect()
+        }
+
+        fn on_completed(step: TransferStep) -> TransferCommand {
+            TransferCommand::Complete { step }
+        }
+
+        fn on_rejected(step: TransferStep, _reason: String) -> TransferCommand {
+            TransferCommand::Reject { step }
+        }
+    }
+
+    /// Process whose step outcomes are not recorded.
+    #[derive(Debug, Default)]
+    struct Stuck;
+
+    impl Aggregate for Stuck {
+        type Command = TransferCommand;
+        type Event = TransferEvent;
+
+        fn init(_id: String) -> Self {
+            Stuck
+        }
+
+        fn aggregate_type() -> &'static str {
+            "Stuck"
+        }
+    }
+
+    impl Handle<TransferCommand> for Stuck {
+        type Error = ();
+
+        fn handle(&self, _cmd: TransferCommand) -> Result<Vec<TransferEvent>, Self::Error> {
+            Ok(vec![])
+        }
+    }
+
+    impl Apply<TransferStarted> for Stuck {
+        fn apply(&mut self, _event: TransferStarted) {}
+    }
+
+    impl Apply<StepCompleted> for Stuck {
+        fn apply(&mut self, _event: StepCompleted) {}
+    }
+
+    impl Apply<StepRejected> for Stuck {
+        fn apply(&mut self, _event: StepRejected) {}
+    }
+
+    impl ProcessManager for Stuck {
+        type Input = TransferRequested;
+        type Step = TransferStep;
+
+        fn correlation_id(input: &TransferRequested) -> Option<String> {
+            Some(input.transfer_id.clone())
+        }
+
+        fn on_input(input: TransferRequested) -> TransferCommand {
+            Transfer::on_input(input)
+        }
+
+        fn pending_steps(&self) -> Vec<TransferStep> {
+            vec![TransferStep::Refund {
+                account: "saga_D".to_string(),
+                amount: 1,
+            }]
+        }
+
+        fn on_completed(step: TransferStep) -> TransferCommand {
+            TransferCommand::Complete { step }
+        }
+
+        fn on_rejected(step: TransferStep, _reason: String) -> TransferCommand {
+            TransferCommand::Reject { step }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_stops_on_unrecorded_step() {
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+        let input = TransferRequested {
+            transfer_id: "stuck_1".to_string(),
+            from: "saga_D".to_string(),
+            to: "saga_D".to_string(),
+            amount: 1,
+        };
+
+        let result = tsuzuri.process::<Stuck>(input).await;
+        assert!(matches!(result, Err(ProcessError::StepStillPending { id }) if id == "stuck_1"));
+    }
+
+    #[tokio::test]
+    async fn test_process_compensates_rejected_step() -> Result<(), StoreError> {
+        let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new())).build();
+
+        let open = |id: &'static str| {
+            let tsuzuri = &tsuzuri;
+            async move {
+                let cmd = BankAccountCommand::OpenAccount(OpenAccount {});
+                tsuzuri.execute::<BankAccount>(id, cmd).await.unwrap();
+                let cmd = BankAccountCommand::DepositFunds(DepositFunds { amount: 100 });
+                tsuzuri.execute::<BankAccount>(id, cmd).await.unwrap();
+            }
+        };
+        open("saga_A").await;
+        open("saga_B").await;
+
+        let transfer = |transfer_id: &str, to: &str| TransferRequested {
+            transfer_id: transfer_id.to_string(),
+            from: "saga_A".to_string(),
+            to: to.to_string(),
+            amount: 30,
+        };
+
+        // 入金先が存在する場合は出金と入金が行われる
+        tsuzuri
+            .process::<Transfer>(transfer("transfer_1", "saga_B"))
+            .await
+            .unwrap();
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<BankAccount>("saga_A"), 0)
+                .await?
+                .len(),
+            3
+        );
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<BankAccount>("saga_B"), 0)
+                .await?
+                .len(),
+            3
+        );
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<Transfer>("transfer_1"), 0)
+                .await?
+                .len(),
+            3
+        );
+
+        // ステップのコマンドには、プロセスの ID とステップの位置が記録される
+        let payloads = tsuzuri
+            .es_read()
+            .read_to_latest(&StreamId::of::<BankAccount>("saga_B"), 0)
+            .await?;
+        let metadata = payloads.last().unwrap().event_metadata().unwrap();
+        assert_eq!(metadata.correlation_id.as_deref(), Some("transfer_1"));
+        assert_eq!(metadata.causation_id.as_deref(), Some("transfer_1/2"));
+
+        // 入金先が開設されていない場合は出金が取り消される
+        tsuzuri
+            .process::<Transfer>(transfer("transfer_2", "saga_C"))
+            .await
+            .unwrap();
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<BankAccount>("saga_A"), 0)
+                .await?
+                .len(),
+            5
+        );
+        assert!(tsuzuri
+            .es_read()
+            .read_to_latest(&StreamId::of::<BankAccount>("saga_C"), 0)
+            .await?
+            .is_empty());
+        assert_eq!(
+            tsuzuri
+                .es_read()
+                .read_to_latest(&StreamId::of::<Transfer>("transfer_2"), 0)
+                .await?
+                .len(),
+            4
+        );
+
+        Ok(())
+    }
+}
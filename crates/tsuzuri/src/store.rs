@@ -1,2 +1,5 @@
+pub mod future;
+pub mod page;
 pub mod payload;
+pub mod stream;
 pub mod sync;
@@ -0,0 +1,198 @@
+//! Streaming reads.
+//!
+//! The reads of a [`Reader`] return every payload at once. The functions here
+//! return a [`Stream`] instead, fetching page by page with [`Reader::read_page`]
+//! and [`Reader::read_all_page`], so replaying a large stream only keeps one
+//! page in memory:
+//!
+//! ```ignore
+//! let mut payloads = read_stream(&store, &stream, ReadOptions::forward().max_count(500));
+//! while let Some(payload) = payloads.try_next().await? {
+//!     // ...
+//! }
+//! ```
+
+use crate::store::{
+    page::{Page, ReadOptions},
+    payload::Payload,
+    stream::StreamId,
+    sync::{error::StoreError, reader::Reader},
+};
+use futures_util::{stream, Stream, TryStreamExt};
+use std::future::Future;
+
+/// Number of payloads fetched at once when [`ReadOptions::max_count`] is not set.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Reads every payload of the stream from the cursor on, in the direction of `options`.
+///
+/// `options.max_count` is the size of the pages fetched from the store, not a
+/// limit on the number of payloads; use [`StreamExt::take`](futures_util::StreamExt::take)
+/// for that.
+pub fn read_stream<'a, R>(
+    reader: &'a R,
+    stream: &'a StreamId,
+    options: ReadOptions,
+) -> impl Stream<Item = Result<Payload, StoreError>> + Send + 'a
+where
+    R: Reader + ?Sized,
+{
+    paged(options, move |options| async move {
+        reader.read_page(stream, &options).await
+    })
+}
+
+/// Reads the payloads of every stream from the global position of the cursor on,
+/// fetching them with [`Reader::read_all_page`].
+pub fn read_all<R>(reader: &R, options: ReadOptions) -> impl Stream<Item = Result<Payload, StoreError>> + Send + '_
+where
+    R: Reader + ?Sized,
+{
+    paged(options, move |options| async move {
+        reader.read_all_page(None, &options).await
+    })
+}
+
+/// Reads the payloads of every stream of an aggregate type, like [`read_all`].
+pub fn read_category<'a, R>(
+    reader: &'a R,
+    aggregate_type: &'a str,
+    options: ReadOptions,
+) -> impl Stream<Item = Result<Payload, StoreError>> + Send + 'a
+where
+    R: Reader + ?Sized,
+{
+    paged(options, move |options| async move {
+        reader.read_all_page(Some(aggregate_type), &options).await
+    })
+}
+
+/// Yields the payloads of the pages returned by `read_page`, following [`Page::next`]
+/// until the last page.
+pub(crate) fn paged<'a, F, Fut>(
+    options: ReadOptions,
+    read_page: F,
+) -> impl Stream<Item = Result<Payload, StoreError>> + Send + 'a
+where
+    F: Fn(ReadOptions) -> Fut + Send + 'a,
+    Fut: Future<Output = Result<Page, StoreError>> + Send + 'a,
+{
+    let page_size = options.max_count.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+    let options = options.max_count(page_size);
+    stream::try_unfold((Some(options), read_page), |(options, read_page)| async move {
+        let Some(options) = options else {
+            return Ok(None);
+        };
+        let page = read_page(options).await?;
+        let next = page.next.map(|cursor| options.cursor(cursor));
+        Ok(Some((
+            stream::iter(page.payloads.into_iter().map(Ok)),
+            (next, read_page),
+        )))
+    })
+    .try_flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::sync::{memory_store::MemoryStore, writer::Writer};
+    use async_trait::async_trait;
+    use futures_util::StreamExt;
+    use std::{
+        collections::BTreeSet,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Counts the payloads returned by a reader relying on the default `read_page`.
+    struct Counting {
+        store: MemoryStore,
+        read: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Reader for Counting {
+        async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+            self.read.fetch_add(1, Ordering::SeqCst);
+            self.store.read(stream, seq).await
+        }
+
+        async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+            let payloads = self.store.read_to(stream, from, to).await?;
+            self.read.fetch_add(payloads.len(), Ordering::SeqCst);
+            Ok(payloads)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_read_page_reads_only_the_page() -> Result<(), StoreError> {
+        let reader = Counting {
+            store: MemoryStore::new(),
+            read: AtomicUsize::new(0),
+        };
+        let stream = StreamId::new("Future", "future_2_A");
+        for sequence in 1..=1000 {
+            reader
+                .store
+                .write(&stream, Payload::new(&stream, sequence, vec![], None).unwrap())
+                .await?;
+        }
+
+        // 各ページで1件多く読むだけで、ストリーム全体を読み直さない
+        let payloads = read_stream(&reader, &stream, ReadOptions::forward())
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(payloads.len(), 1000);
+        assert_eq!(reader.read.swap(0, Ordering::SeqCst), 1000 + 9);
+
+        let payloads = read_stream(&reader, &stream, ReadOptions::backward().cursor(1000))
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(payloads.first().map(|payload| payload.sequence), Some(1000));
+        assert_eq!(reader.read.load(Ordering::SeqCst), 1000 + 9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_stream_in_pages() -> Result<(), StoreError> {
+        let store = MemoryStore::new();
+        let (a, b) = (
+            StreamId::new("Future", "future_1_A"),
+            StreamId::new("Future", "future_1_B"),
+        );
+        for sequence in 1..=7 {
+            store
+                .write(&a, Payload::new(&a, sequence, vec![], None).unwrap())
+                .await?;
+        }
+        store.write(&b, Payload::new(&b, 1, vec![], None).unwrap()).await?;
+
+        let sequences = |payloads: Vec<Payload>| payloads.iter().map(|payload| payload.sequence).collect::<Vec<_>>();
+        let payloads = read_stream(&store, &a, ReadOptions::forward().max_count(3))
+            .try_collect()
+            .await?;
+        assert_eq!(sequences(payloads), [1, 2, 3, 4, 5, 6, 7]);
+        let payloads = read_stream(&store, &a, ReadOptions::backward().max_count(2).cursor(5))
+            .try_collect()
+            .await?;
+        assert_eq!(sequences(payloads), [5, 4, 3, 2, 1]);
+        let payloads = read_stream(&store, &a, ReadOptions::backward().max_count(2))
+            .take(3)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(sequences(payloads), [7, 6, 5]);
+        let missing = StreamId::new("Future", "future_1_C");
+        assert_eq!(read_stream(&store, &missing, ReadOptions::forward()).count().await, 0);
+
+        let options = ReadOptions::forward().max_count(3);
+        assert_eq!(read_all(&store, options).try_collect::<Vec<_>>().await?.len(), 8);
+        let payloads = read_category(&store, "Future", ReadOptions::backward().max_count(3))
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(payloads.first().map(|payload| &payload.id), Some(&b.id));
+        assert_eq!(payloads.len(), 8);
+        assert_eq!(read_category(&store, "Other", options).count().await, 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! Paged reads of a stream.
+
+use crate::store::payload::Payload;
+
+/// Order in which the payloads of a stream are read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// From the oldest payload to the latest.
+    #[default]
+    Forward,
+    /// From the latest payload to the oldest.
+    Backward,
+}
+
+/// Options of [`Reader::read_page`](crate::store::sync::reader::Reader::read_page).
+///
+/// ```ignore
+/// // 最新の20件
+/// let page = read_store.read_page(&stream, &ReadOptions::backward().max_count(20)).await?;
+/// // 続きの20件
+/// let options = ReadOptions::backward().max_count(20).cursor(page.next.unwrap());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReadOptions {
+    pub direction: Direction,
+    /// Maximum number of payloads of the page, unbounded when `None`. A page of
+    /// at most 0 payloads is empty and has no next page.
+    pub max_count: Option<usize>,
+    /// Sequence of the first payload of the page, usually the [`Page::next`] of the
+    /// previous page. Reads start at the beginning of the stream, or at its end
+    /// when reading backward, when `None`.
+    pub cursor: Option<usize>,
+}
+
+impl ReadOptions {
+    pub fn forward() -> Self {
+        Self::default()
+    }
+
+    pub fn backward() -> Self {
+        Self {
+            direction: Direction::Backward,
+            ..Self::default()
+        }
+    }
+
+    pub fn max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    pub fn cursor(mut self, cursor: usize) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Builds the page from the payloads of the stream from the cursor on, in
+    /// ascending sequence order, e.g. the sequences `cursor..` when reading
+    /// forward and `..=cursor` when reading backward.
+    ///
+    /// Payloads after the page are not consumed, so stores can pass a lazy iterator.
+    pub fn paginate<I>(&self, payloads: I) -> Page
+    where
+        I: IntoIterator<Item = Payload>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        self.paginate_by(payloads.into_iter().map(|payload| (payload.sequence, payload)))
+    }
+
+    /// Like [`paginate`](Self::paginate), with payloads keyed by their cursor, e.g.
+    /// their global position for [`Reader::read_all_page`](crate::store::sync::reader::Reader::read_all_page).
+    pub fn paginate_by<I>(&self, payloads: I) -> Page
+    where
+        I: IntoIterator<Item = (usize, Payload)>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let payloads = payloads.into_iter();
+        match self.direction {
+            Direction::Forward => self.take(payloads),
+            Direction::Backward => self.take(payloads.rev()),
+        }
+    }
+
+    fn take(&self, mut payloads: impl Iterator<Item = (usize, Payload)>) -> Page {
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        // 0件のページで次のカーソルを返すと、同じページを読み続けてしまう
+        if max_count == 0 {
+            return Page::default();
+        }
+        let page: Vec<_> = payloads.by_ref().take(max_count).map(|(_, payload)| payload).collect();
+        Page {
+            payloads: page,
+            next: payloads.next().map(|(cursor, _)| cursor),
+        }
+    }
+}
+
+/// Payloads read by [`Reader::read_page`](crate::store::sync::reader::Reader::read_page)
+/// or [`Reader::read_all_page`](crate::store::sync::reader::Reader::read_all_page),
+/// in the order of the [`Direction`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Page {
+    pub payloads: Vec<Payload>,
+    /// Cursor of the next page, `None` when the page reaches the end of the read.
+    pub next: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::stream::StreamId;
+
+    #[test]
+    fn test_paginate() {
+        let stream = StreamId::new("Page", "page_1");
+        let payloads: Vec<_> = (1..=5)
+            .map(|sequence| Payload::new(&stream, sequence, b"{}".to_vec(), None).unwrap())
+            .collect();
+        let sequences = |page: &Page| page.payloads.iter().map(|payload| payload.sequence).collect::<Vec<_>>();
+
+        let page = ReadOptions::forward().max_count(2).paginate(payloads.clone());
+        assert_eq!((sequences(&page), page.next), (vec![1, 2], Some(3)));
+        let page = ReadOptions::backward().max_count(2).paginate(payloads.clone());
+        assert_eq!((sequences(&page), page.next), (vec![5, 4], Some(3)));
+        let page = ReadOptions::backward().max_count(5).paginate(payloads.clone());
+        assert_eq!((sequences(&page), page.next), (vec![5, 4, 3, 2, 1], None));
+        let page = ReadOptions::forward().max_count(0).paginate(payloads.clone());
+        assert_eq!((sequences(&page), page.next), (vec![], None));
+        let page = ReadOptions::forward().paginate(payloads);
+        assert_eq!((sequences(&page).len(), page.next), (5, None));
+    }
+}
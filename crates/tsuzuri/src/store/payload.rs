@@ -1,15 +1,36 @@
-use std::cmp::Ordering;
+use crate::{clock, store::stream::StreamId};
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    sync::{Mutex, PoisonError},
+};
 // use std::error::Error;
 use time::OffsetDateTime;
 
-#[derive(Debug)]
+pub use uuid::Uuid;
+use uuid::{ContextV7, Timestamp};
+
+// 同じミリ秒内に作られた ID もプロセス内で単調増加させる
+static CONTEXT: Mutex<(u128, ContextV7)> = Mutex::new((0, ContextV7::new()));
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to build payload")]
 // pub struct SerializeError(Box<dyn Error + Sync + Send>);
 pub struct SerializeError;
 
 /// Basic format of the data to be saved.
+///
+/// Payloads are identified by their sequence and `event_id`, so a payload read back
+/// from a store equals the written one even when the store keeps coarser timestamps.
+/// They are ordered by the same fields.
 #[derive(Debug, Clone)]
 // #[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 pub struct Payload {
+    /// Globally unique identifier of the event, a UUIDv7 sortable by creation time,
+    /// taken from the same [`clock`] as `created_at`
+    pub event_id: Uuid,
+    /// Name of the aggregate type
+    pub aggregate_type: String,
     /// Aggregate entity identifier
     pub id: String,
     /// The sequence number for an aggregate instance.
@@ -22,22 +43,55 @@ pub struct Payload {
 }
 
 impl Payload {
-    pub fn new(id: &str, seq: usize, event: Vec<u8>, metadata: Option<Vec<u8>>) -> Result<Self, SerializeError> {
+    pub fn new(
+        stream: &StreamId,
+        seq: usize,
+        event: Vec<u8>,
+        metadata: Option<Vec<u8>>,
+    ) -> Result<Self, SerializeError> {
+        let created_at = clock::now();
         Ok(Self {
-            id: id.to_string(),
+            event_id: event_id(created_at)?,
+            aggregate_type: stream.aggregate_type.clone(),
+            id: stream.id.clone(),
             sequence: seq,
             bytes: event,
             metadata,
-            created_at: OffsetDateTime::now_utc(),
+            created_at,
         })
     }
+
+    /// Returns the stream the payload belongs to.
+    pub fn stream_id(&self) -> StreamId {
+        StreamId::new(&self.aggregate_type, &self.id)
+    }
+}
+
+/// Builds a UUIDv7 whose timestamp is `at`, the time of the [`clock`].
+fn event_id(at: OffsetDateTime) -> Result<Uuid, SerializeError> {
+    let seconds = u64::try_from(at.unix_timestamp()).map_err(|_| SerializeError)?;
+    let millis = (at.unix_timestamp_nanos() / 1_000_000) as u128;
+    let mut context = CONTEXT.lock().unwrap_or_else(PoisonError::into_inner);
+    // ContextV7 は時刻を巻き戻さないため、時計が戻った場合は作り直して時計の時刻を使う
+    if millis < context.0 {
+        context.1 = ContextV7::new();
+    }
+    context.0 = millis;
+    let timestamp = Timestamp::from_unix(&context.1, seconds, at.nanosecond());
+    Ok(Uuid::new_v7(timestamp))
 }
 
 impl Eq for Payload {}
 
 impl PartialEq<Self> for Payload {
     fn eq(&self, other: &Self) -> bool {
-        self.sequence.eq(&other.sequence) && self.id.eq(&other.id) && self.created_at.eq(&other.created_at)
+        self.sequence == other.sequence && self.event_id == other.event_id
+    }
+}
+
+impl Hash for Payload {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.event_id.hash(state);
     }
 }
 
@@ -49,9 +103,50 @@ impl PartialOrd<Self> for Payload {
 
 impl Ord for Payload {
     fn cmp(&self, other: &Self) -> Ordering {
+        // 等価性と同じく sequence と event_id で比較する
         self.sequence
             .cmp(&other.sequence)
-            .then_with(|| self.created_at.cmp(&other.created_at))
-            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| self.event_id.cmp(&other.event_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use std::{collections::BTreeSet, sync::Arc};
+
+    #[test]
+    fn test_payload_identity() {
+        let stream = StreamId::new("Payload", "payload_1");
+        let first = Payload::new(&stream, 1, vec![], None).unwrap();
+        let second = Payload::new(&stream, 2, vec![], None).unwrap();
+        assert_ne!(first.event_id, second.event_id);
+        assert!(first.event_id < second.event_id);
+
+        // タイムスタンプの精度が低いストアを経由しても同じイベントとして扱う
+        let mut read = first.clone();
+        read.created_at = read.created_at.replace_nanosecond(0).unwrap();
+        assert_eq!(read, first);
+        assert_eq!(read.cmp(&first), Ordering::Equal);
+        let mut moved = first.clone();
+        moved.sequence = 3;
+        assert_ne!(moved, first);
+        assert_ne!(moved.cmp(&first), Ordering::Equal);
+
+        let set: BTreeSet<_> = [second.clone(), read, first.clone()].into_iter().collect();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), [first, second]);
+    }
+
+    #[test]
+    fn test_event_id_uses_clock() {
+        let stream = StreamId::new("Payload", "payload_2");
+        let at = OffsetDateTime::from_unix_timestamp_nanos(1_709_283_600_123_000_000).unwrap();
+        let clock: Arc<dyn Clock> = Arc::new(ManualClock::new(at));
+        let payload = clock::scope(&clock, || Payload::new(&stream, 1, vec![], None)).unwrap();
+
+        let (seconds, nanos) = payload.event_id.get_timestamp().unwrap().to_unix();
+        assert_eq!((seconds as i64, nanos), (at.unix_timestamp(), 123_000_000));
+        assert_eq!(payload.created_at, at);
     }
 }
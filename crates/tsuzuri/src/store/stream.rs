@@ -0,0 +1,35 @@
+use crate::aggregate::Aggregate;
+use std::fmt;
+
+/// Identity of an event stream: the aggregate type and the id of the aggregate.
+///
+/// Aggregates of different types may share an id without their streams
+/// colliding. All the streams of one aggregate type form its category, see
+/// [`Reader::read_category`](crate::store::sync::reader::Reader::read_category).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    /// Name of the aggregate type, see [`Aggregate::aggregate_type`].
+    pub aggregate_type: String,
+    /// Aggregate entity identifier
+    pub id: String,
+}
+
+impl StreamId {
+    pub fn new(aggregate_type: impl Into<String>, id: impl Into<String>) -> Self {
+        Self {
+            aggregate_type: aggregate_type.into(),
+            id: id.into(),
+        }
+    }
+
+    /// Returns the stream of the aggregate `T` with the given id.
+    pub fn of<T: Aggregate>(id: impl Into<String>) -> Self {
+        Self::new(T::aggregate_type(), id)
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.aggregate_type, self.id)
+    }
+}
@@ -1,5 +1,8 @@
 pub mod error;
 pub mod event_store;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod layer;
 pub mod memory_store;
 pub mod query_store;
 pub mod reader;
@@ -1,4 +1,5 @@
-use std::error::Error;
+use crate::store::stream::StreamId;
+use std::{error::Error, io};
 
 #[derive(Debug, thiserror::Error)]
 pub enum StoreError {
@@ -8,4 +9,53 @@ pub enum StoreError {
     Write(#[source] Box<dyn Error + Sync + Send>),
     #[error("Failed to read data: {0}")]
     Read(#[source] Box<dyn Error + Sync + Send>),
+    /// Another writer has already stored a payload with the same sequence.
+    #[error("Payload with sequence {sequence} already exists for stream {stream}")]
+    Conflict { stream: StreamId, sequence: usize },
+}
+
+impl StoreError {
+    /// Returns the errors of the source chain, from the wrapped error on.
+    pub(crate) fn sources<'a>(&'a self) -> impl Iterator<Item = &'a (dyn Error + 'static)> {
+        let source: Option<&'a (dyn Error + 'static)> = match self {
+            StoreError::Setup(source) | StoreError::Write(source) | StoreError::Read(source) => Some(source.as_ref()),
+            StoreError::Conflict { .. } => None,
+        };
+        std::iter::successors(source, |err: &&'a (dyn Error + 'static)| (*err).source())
+    }
+
+    /// Returns the kind of the first I/O error of the source chain.
+    #[cfg(feature = "grpc")]
+    pub(crate) fn io_kind(&self) -> Option<io::ErrorKind> {
+        self.sources()
+            .find_map(|err| err.downcast_ref::<io::Error>())
+            .map(io::Error::kind)
+    }
+
+    /// Whether a read failed because the requested data does not exist.
+    #[cfg(feature = "axum")]
+    pub(crate) fn is_not_found(&self) -> bool {
+        let StoreError::Read(_) = self else {
+            return false;
+        };
+        self.sources().any(|err| {
+            #[cfg(feature = "grpc")]
+            if let Some(status) = err.downcast_ref::<tonic::Status>() {
+                return status.code() == tonic::Code::NotFound;
+            }
+            err.downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::NotFound)
+        })
+    }
+}
+
+/// Whether an I/O error of this kind may succeed when retried.
+pub(crate) fn is_transient_kind(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::WouldBlock
+    )
 }
@@ -1,4 +1,5 @@
 use crate::store::sync::{
+    layer::{Layer, StoreBuilder},
     reader::{DefaultQuery, ReadStore, Reader},
     writer::{WriteStore, Writer},
 };
@@ -14,8 +15,23 @@ impl EventStore {
         S: Reader + Writer + Clone + 'static,
     {
         EventStore {
-            read_store: ReadStore::new(store.clone(), DefaultQuery {}),
-            write_store: WriteStore::new(store),
+            read_store: ReadStore::new(store.clone(), DefaultQuery {}).labeled("event"),
+            write_store: WriteStore::new(store).labeled("event"),
         }
     }
+
+    /// Starts building an EventStore whose store is wrapped in layers.
+    pub fn builder<S>(store: S) -> StoreBuilder<S, EventStore> {
+        StoreBuilder::new(store)
+    }
+}
+
+impl<S, L> StoreBuilder<S, EventStore, L>
+where
+    L: Layer<S>,
+    L::Store: Reader + Writer + Clone + 'static,
+{
+    pub fn build(self) -> EventStore {
+        EventStore::new(self.into_store())
+    }
 }
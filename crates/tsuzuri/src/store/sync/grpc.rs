@@ -0,0 +1,322 @@
+//! An event store shared over gRPC.
+//!
+//! [`EventStoreService`] serves any [`Reader`] + [`Writer`] store, and
+//! [`GrpcStore`] connects to it and implements [`Reader`] and [`Writer`]
+//! itself, so several services can use one event store process:
+//!
+//! ```ignore
+//! // server
+//! tonic::transport::Server::builder()
+//!     .add_service(EventStoreService::new(MemoryStore::new()).into_server())
+//!     .serve(addr)
+//!     .await?;
+//!
+//! // client
+//! let event_store = EventStore::new(GrpcStore::connect("http://[::1]:50051").await?);
+//! ```
+
+use crate::store::{
+    page::{Direction, Page},
+    payload::{Payload, Uuid},
+    stream::StreamId,
+    sync::error::{is_transient_kind, StoreError},
+};
+use std::io;
+use time::OffsetDateTime;
+use tonic::{Code, Status};
+
+#[cfg(doc)]
+use crate::store::sync::{reader::Reader, writer::Writer};
+
+pub mod client;
+pub mod server;
+
+pub use client::GrpcStore;
+pub use server::EventStoreService;
+
+/// Messages and services generated from `proto/event_store.proto`.
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("tsuzuri.event_store");
+}
+
+impl From<&StreamId> for proto::StreamId {
+    fn from(stream: &StreamId) -> Self {
+        Self {
+            aggregate_type: stream.aggregate_type.clone(),
+            id: stream.id.clone(),
+        }
+    }
+}
+
+impl From<proto::StreamId> for StreamId {
+    fn from(stream: proto::StreamId) -> Self {
+        Self::new(stream.aggregate_type, stream.id)
+    }
+}
+
+/// Converts the stream of a request, which is required.
+impl TryFrom<Option<proto::StreamId>> for StreamId {
+    type Error = Status;
+
+    fn try_from(stream: Option<proto::StreamId>) -> Result<Self, Self::Error> {
+        stream
+            .map(StreamId::from)
+            .ok_or_else(|| Status::invalid_argument("stream is required"))
+    }
+}
+
+impl From<Payload> for proto::Payload {
+    fn from(payload: Payload) -> Self {
+        Self {
+            event_id: payload.event_id.to_string(),
+            aggregate_type: payload.aggregate_type,
+            id: payload.id,
+            sequence: payload.sequence as u64,
+            bytes: payload.bytes,
+            metadata: payload.metadata,
+            created_at: payload.created_at.unix_timestamp_nanos() as i64,
+        }
+    }
+}
+
+impl TryFrom<proto::Payload> for Payload {
+    type Error = Status;
+
+    fn try_from(payload: proto::Payload) -> Result<Self, Self::Error> {
+        let created_at = OffsetDateTime::from_unix_timestamp_nanos(payload.created_at as i128)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let event_id = Uuid::parse_str(&payload.event_id).map_err(|err| Status::invalid_argument(err.to_string()))?;
+        Ok(Self {
+            event_id,
+            aggregate_type: payload.aggregate_type,
+            id: payload.id,
+            sequence: payload.sequence as usize,
+            bytes: payload.bytes,
+            metadata: payload.metadata,
+            created_at,
+        })
+    }
+}
+
+impl From<Direction> for proto::Direction {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Forward => Self::Forward,
+            Direction::Backward => Self::Backward,
+        }
+    }
+}
+
+impl From<proto::Direction> for Direction {
+    fn from(direction: proto::Direction) -> Self {
+        match direction {
+            proto::Direction::Forward => Self::Forward,
+            proto::Direction::Backward => Self::Backward,
+        }
+    }
+}
+
+impl From<Page> for proto::Page {
+    fn from(page: Page) -> Self {
+        Self {
+            payloads: page.payloads.into_iter().map(Into::into).collect(),
+            next: page.next.map(|next| next as u64),
+        }
+    }
+}
+
+impl TryFrom<proto::Page> for Page {
+    type Error = Status;
+
+    fn try_from(page: proto::Page) -> Result<Self, Self::Error> {
+        Ok(Self {
+            payloads: page
+                .payloads
+                .into_iter()
+                .map(Payload::try_from)
+                .collect::<Result<_, _>>()?,
+            next: page.next.map(|next| next as usize),
+        })
+    }
+}
+
+/// Maps store errors to status codes, so clients only retry transient failures.
+impl From<StoreError> for Status {
+    fn from(err: StoreError) -> Self {
+        // 競合は ABORTED として返し、クライアント側で StoreError::Conflict に戻す
+        if let StoreError::Conflict { .. } = err {
+            return Status::aborted(err.to_string());
+        }
+        // 別のサーバーから受け取ったエラーはそのコードを引き継ぐ
+        if let Some(status) = err.sources().find_map(|err| err.downcast_ref::<Status>()) {
+            return Status::new(status.code(), err.to_string());
+        }
+        let code = match err.io_kind() {
+            Some(io::ErrorKind::NotFound) => Code::NotFound,
+            Some(io::ErrorKind::InvalidInput) => Code::InvalidArgument,
+            Some(io::ErrorKind::Unsupported) => Code::Unimplemented,
+            Some(kind) if is_transient_kind(kind) => Code::Unavailable,
+            _ if err.sources().any(|err| err.is::<tokio::time::error::Elapsed>()) => Code::DeadlineExceeded,
+            _ => Code::Internal,
+        };
+        Status::new(code, err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        page::ReadOptions,
+        sync::{memory_store::MemoryStore, reader::Reader, writer::Writer},
+    };
+    use futures_util::StreamExt;
+    use std::collections::BTreeSet;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    /// Fails every read with an I/O error of the kind named by the stream id.
+    #[derive(Clone)]
+    struct Failing;
+
+    #[async_trait::async_trait]
+    impl Reader for Failing {
+        async fn read(&self, stream: &StreamId, _seq: usize) -> Result<Payload, StoreError> {
+            let kind = match stream.id.as_str() {
+                "not_found" => io::ErrorKind::NotFound,
+                "invalid_input" => io::ErrorKind::InvalidInput,
+                "unsupported" => io::ErrorKind::Unsupported,
+                "timed_out" => io::ErrorKind::TimedOut,
+                _ => io::ErrorKind::Other,
+            };
+            Err(StoreError::Read(Box::new(io::Error::from(kind))))
+        }
+
+        async fn read_to(&self, stream: &StreamId, from: usize, _to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+            self.read(stream, from).await.map(|payload| BTreeSet::from([payload]))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Writer for Failing {
+        async fn append(&self, _: &StreamId, _: usize, _: Vec<Payload>) -> Result<(), StoreError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_codes() -> Result<(), StoreError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(EventStoreService::new(Failing).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let store = GrpcStore::connect(format!("http://{addr}")).await?;
+
+        for (id, code) in [
+            ("not_found", Code::NotFound),
+            ("invalid_input", Code::InvalidArgument),
+            ("unsupported", Code::Unimplemented),
+            ("timed_out", Code::Unavailable),
+            ("other", Code::Internal),
+        ] {
+            let err = store.read(&StreamId::new("Status", id), 1).await.unwrap_err();
+            let status = err.sources().find_map(|err| err.downcast_ref::<Status>()).unwrap();
+            assert_eq!(status.code(), code, "{id}");
+        }
+        // 読み込み全体に対応していないストアは UNIMPLEMENTED を返す
+        let err = store.read_all().await.unwrap_err();
+        let status = err.sources().find_map(|err| err.downcast_ref::<Status>()).unwrap();
+        assert_eq!(status.code(), Code::Unimplemented);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_ahead_of_head() -> Result<(), StoreError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(EventStoreService::new(MemoryStore::new()).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let store = GrpcStore::connect(format!("http://{addr}")).await?;
+
+        let stream = StreamId::new("Grpc", "grpc_2_A");
+        let mut subscription = Box::pin(store.subscribe(Some(&stream), 3).await?);
+        for sequence in 1..=3 {
+            store
+                .write(&stream, Payload::new(&stream, sequence, vec![], None).unwrap())
+                .await?;
+        }
+        assert_eq!(subscription.next().await.unwrap()?.sequence, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_store() -> Result<(), StoreError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(EventStoreService::new(MemoryStore::new()).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let store = GrpcStore::connect(format!("http://{addr}")).await?;
+
+        let (a, b) = (StreamId::new("Grpc", "grpc_1_A"), StreamId::new("Grpc", "grpc_1_B"));
+        let mut subscription = Box::pin(store.subscribe(Some(&a), 0).await?);
+        let payload = |seq| Payload::new(&a, seq, vec![seq as u8], None).unwrap();
+        store.write(&a, payload(1)).await?;
+        store.write(&a, payload(2)).await?;
+        store.write(&b, Payload::new(&b, 1, vec![], None).unwrap()).await?;
+        // 同じ id でも集約の型が異なれば別のストリームになる
+        let other = StreamId::new("Other", "grpc_1_A");
+        store
+            .write(&other, Payload::new(&other, 1, vec![], None).unwrap())
+            .await?;
+
+        assert!(matches!(
+            store.write(&a, payload(2)).await,
+            Err(StoreError::Conflict { sequence: 2, .. })
+        ));
+        assert_eq!(store.read(&a, 2).await?.bytes, vec![2]);
+        let written = payload(3);
+        store.write(&a, written.clone()).await?;
+        // 応答を失った書き込みの再送は成功し、購読者に再び通知しない
+        store.write(&a, written.clone()).await?;
+        assert_eq!(store.read(&a, 3).await?, written);
+        assert_eq!(store.read_to_latest(&a, 0).await?.len(), 3);
+        assert_eq!(store.read_to(&a, 0, 2).await?.len(), 1);
+        assert_eq!(store.read_all().await?.len(), 5);
+        assert_eq!(store.read_category("Grpc").await?.len(), 4);
+
+        let page = store.read_page(&a, &ReadOptions::backward().max_count(2)).await?;
+        let sequences: Vec<_> = page.payloads.iter().map(|payload| payload.sequence).collect();
+        assert_eq!((sequences, page.next), (vec![3, 2], Some(1)));
+        let page = store.read_page(&a, &ReadOptions::forward().cursor(2)).await?;
+        assert_eq!((page.payloads.len(), page.next), (2, None));
+        let page = store
+            .read_all_page(Some("Grpc"), &ReadOptions::forward().max_count(2).cursor(2))
+            .await?;
+        let ids: Vec<_> = page
+            .payloads
+            .iter()
+            .map(|payload| (payload.id.as_str(), payload.sequence))
+            .collect();
+        assert_eq!((ids, page.next), (vec![("grpc_1_A", 2), ("grpc_1_B", 1)], Some(5)));
+
+        store.write(&a, payload(4)).await?;
+        let mut sequences = Vec::new();
+        for _ in 0..4 {
+            sequences.push(subscription.next().await.unwrap()?.sequence);
+        }
+        assert_eq!(sequences, [1, 2, 3, 4]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use crate::store::{
+    page::{Page, ReadOptions},
+    payload::Payload,
+    stream::StreamId,
+    sync::{
+        error::StoreError,
+        grpc::proto::{self, event_store_client::EventStoreClient},
+        reader::Reader,
+        writer::Writer,
+    },
+};
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use std::collections::BTreeSet;
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code, Status,
+};
+
+/// A store backed by a remote [`EventStoreService`](super::EventStoreService).
+#[derive(Clone, Debug)]
+pub struct GrpcStore {
+    client: EventStoreClient<Channel>,
+}
+
+impl GrpcStore {
+    pub async fn connect<D>(dst: D) -> Result<Self, StoreError>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let endpoint = dst.try_into().map_err(|err| StoreError::Setup(Box::new(err)))?;
+        let channel = endpoint
+            .connect()
+            .await
+            .map_err(|err| StoreError::Setup(Box::new(err)))?;
+        Ok(Self::new(channel))
+    }
+
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            client: EventStoreClient::new(channel),
+        }
+    }
+
+    /// Streams the payloads appended through the server.
+    ///
+    /// When `stream` is given, only that stream is followed, starting with its stored payloads from `from`.
+    pub async fn subscribe(
+        &self,
+        stream: Option<&StreamId>,
+        from: usize,
+    ) -> Result<impl Stream<Item = Result<Payload, StoreError>>, StoreError> {
+        let request = proto::SubscribeRequest {
+            stream: stream.map(Into::into),
+            from: from as u64,
+        };
+        let stream = self.client.clone().subscribe(request).await.map_err(read_error)?;
+        Ok(stream
+            .into_inner()
+            .map(|payload| payload.and_then(Payload::try_from).map_err(read_error)))
+    }
+
+    async fn read_all_of(&self, aggregate_type: Option<String>) -> Result<Vec<Payload>, StoreError> {
+        let request = proto::ReadAllRequest { aggregate_type };
+        let payloads = self.client.clone().read_all(request).await.map_err(read_error)?;
+        // グローバルな順序を保つため、BTreeSet を経由せずに変換する
+        payloads
+            .into_inner()
+            .payloads
+            .into_iter()
+            .map(|payload| Payload::try_from(payload).map_err(read_error))
+            .collect()
+    }
+
+    fn into_payloads(payloads: proto::Payloads) -> Result<BTreeSet<Payload>, StoreError> {
+        payloads
+            .payloads
+            .into_iter()
+            .map(|payload| Payload::try_from(payload).map_err(read_error))
+            .collect()
+    }
+}
+
+fn read_error(status: Status) -> StoreError {
+    StoreError::Read(Box::new(status))
+}
+
+#[async_trait]
+impl Reader for GrpcStore {
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        let request = proto::ReadRequest {
+            stream: Some(stream.into()),
+            sequence: seq as u64,
+        };
+        let payload = self.client.clone().read(request).await.map_err(read_error)?;
+        Payload::try_from(payload.into_inner()).map_err(read_error)
+    }
+
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        let request = proto::ReadStreamRequest {
+            stream: Some(stream.into()),
+            from: from as u64,
+            to: Some(to as u64),
+        };
+        let payloads = self.client.clone().read_stream(request).await.map_err(read_error)?;
+        Self::into_payloads(payloads.into_inner())
+    }
+
+    async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        let request = proto::ReadStreamRequest {
+            stream: Some(stream.into()),
+            from: from as u64,
+            to: None,
+        };
+        let payloads = self.client.clone().read_stream(request).await.map_err(read_error)?;
+        Self::into_payloads(payloads.into_inner())
+    }
+
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        let request = proto::ReadPageRequest {
+            stream: Some(stream.into()),
+            direction: proto::Direction::from(options.direction).into(),
+            max_count: options.max_count.map(|max_count| max_count as u64),
+            cursor: options.cursor.map(|cursor| cursor as u64),
+        };
+        let page = self.client.clone().read_page(request).await.map_err(read_error)?;
+        Page::try_from(page.into_inner()).map_err(read_error)
+    }
+
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        let request = proto::ReadAllPageRequest {
+            aggregate_type: aggregate_type.map(str::to_string),
+            direction: proto::Direction::from(options.direction).into(),
+            max_count: options.max_count.map(|max_count| max_count as u64),
+            cursor: options.cursor.map(|cursor| cursor as u64),
+        };
+        let page = self.client.clone().read_all_page(request).await.map_err(read_error)?;
+        Page::try_from(page.into_inner()).map_err(read_error)
+    }
+
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        self.read_all_of(None).await
+    }
+
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        self.read_all_of(Some(aggregate_type.to_string())).await
+    }
+}
+
+#[async_trait]
+impl Writer for GrpcStore {
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        let request = proto::AppendRequest {
+            stream: Some(stream.into()),
+            expected_version: expected_version as u64,
+            payloads: payloads.into_iter().map(Into::into).collect(),
+        };
+        match self.client.clone().append(request).await {
+            Ok(_) => Ok(()),
+            Err(status) if status.code() == Code::Aborted => Err(StoreError::Conflict {
+                stream: stream.clone(),
+                sequence: expected_version + 1,
+            }),
+            Err(status) => Err(StoreError::Write(Box::new(status))),
+        }
+    }
+}
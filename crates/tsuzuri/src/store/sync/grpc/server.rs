@@ -0,0 +1,199 @@
+use crate::store::{
+    page::ReadOptions,
+    payload::Payload,
+    stream::StreamId,
+    sync::{
+        grpc::proto::{
+            self,
+            event_store_server::{EventStore, EventStoreServer},
+        },
+        reader::Reader,
+        writer::Writer,
+    },
+};
+use futures_util::{stream, Stream, StreamExt};
+use std::pin::Pin;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tonic::{Request, Response, Status};
+
+/// Serves a store over gRPC.
+pub struct EventStoreService<S> {
+    store: S,
+    appended: broadcast::Sender<Payload>,
+}
+
+impl<S> EventStoreService<S>
+where
+    S: Reader + Writer,
+{
+    pub fn new(store: S) -> Self {
+        // 購読者が追いつけない場合に保持する件数
+        let (appended, _) = broadcast::channel(1024);
+        Self { store, appended }
+    }
+
+    pub fn into_server(self) -> EventStoreServer<Self> {
+        EventStoreServer::new(self)
+    }
+}
+
+type PayloadStream = Pin<Box<dyn Stream<Item = Result<proto::Payload, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl<S> EventStore for EventStoreService<S>
+where
+    S: Reader + Writer,
+{
+    async fn append(&self, request: Request<proto::AppendRequest>) -> Result<Response<proto::AppendResponse>, Status> {
+        let proto::AppendRequest {
+            stream,
+            expected_version,
+            payloads,
+        } = request.into_inner();
+        let stream = StreamId::try_from(stream)?;
+        let expected_version = expected_version as usize;
+        let payloads = payloads
+            .into_iter()
+            .map(Payload::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        for (sequence, payload) in (expected_version + 1..).zip(&payloads) {
+            if payload.stream_id() != stream || payload.sequence != sequence {
+                return Err(Status::invalid_argument(format!(
+                    "expected payload {} of stream {}, got payload {} of stream {}",
+                    sequence,
+                    stream,
+                    payload.sequence,
+                    payload.stream_id()
+                )));
+            }
+        }
+
+        let version = expected_version + payloads.len();
+        // 同じ event_id のペイロードが保存済みなら、応答を失った要求の再送として保存も通知もしない
+        let stored = self.store.read_to(&stream, expected_version + 1, version + 1).await?;
+        if !payloads.is_empty()
+            && stored.len() == payloads.len()
+            && stored
+                .iter()
+                .zip(&payloads)
+                .all(|(stored, payload)| stored.event_id == payload.event_id)
+        {
+            return Ok(Response::new(proto::AppendResponse {
+                version: version as u64,
+            }));
+        }
+
+        // すべてのペイロードをまとめて書き込み、途中までの書き込みを残さない
+        self.store.append(&stream, expected_version, payloads.clone()).await?;
+        for payload in payloads {
+            // 購読者がいない場合の送信エラーは無視する
+            let _ = self.appended.send(payload);
+        }
+        Ok(Response::new(proto::AppendResponse {
+            version: version as u64,
+        }))
+    }
+
+    async fn read(&self, request: Request<proto::ReadRequest>) -> Result<Response<proto::Payload>, Status> {
+        let proto::ReadRequest { stream, sequence } = request.into_inner();
+        let payload = self.store.read(&StreamId::try_from(stream)?, sequence as usize).await?;
+        Ok(Response::new(payload.into()))
+    }
+
+    async fn read_stream(
+        &self,
+        request: Request<proto::ReadStreamRequest>,
+    ) -> Result<Response<proto::Payloads>, Status> {
+        let proto::ReadStreamRequest { stream, from, to } = request.into_inner();
+        let stream = StreamId::try_from(stream)?;
+        let payloads = match to {
+            Some(to) => self.store.read_to(&stream, from as usize, to as usize).await?,
+            None => self.store.read_to_latest(&stream, from as usize).await?,
+        };
+        Ok(Response::new(proto::Payloads {
+            payloads: payloads.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn read_page(&self, request: Request<proto::ReadPageRequest>) -> Result<Response<proto::Page>, Status> {
+        let request = request.into_inner();
+        let options = ReadOptions {
+            direction: request.direction().into(),
+            max_count: request.max_count.map(|max_count| max_count as usize),
+            cursor: request.cursor.map(|cursor| cursor as usize),
+        };
+        let stream = StreamId::try_from(request.stream)?;
+        let page = self.store.read_page(&stream, &options).await?;
+        Ok(Response::new(page.into()))
+    }
+
+    async fn read_all_page(
+        &self,
+        request: Request<proto::ReadAllPageRequest>,
+    ) -> Result<Response<proto::Page>, Status> {
+        let request = request.into_inner();
+        let options = ReadOptions {
+            direction: request.direction().into(),
+            max_count: request.max_count.map(|max_count| max_count as usize),
+            cursor: request.cursor.map(|cursor| cursor as usize),
+        };
+        let page = self
+            .store
+            .read_all_page(request.aggregate_type.as_deref(), &options)
+            .await?;
+        Ok(Response::new(page.into()))
+    }
+
+    async fn read_all(&self, request: Request<proto::ReadAllRequest>) -> Result<Response<proto::Payloads>, Status> {
+        let payloads = match request.into_inner().aggregate_type {
+            Some(aggregate_type) => self.store.read_category(&aggregate_type).await?,
+            None => self.store.read_all().await?,
+        };
+        Ok(Response::new(proto::Payloads {
+            payloads: payloads.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    type SubscribeStream = PayloadStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let proto::SubscribeRequest { stream, from } = request.into_inner();
+        let (stream, from) = (stream.map(StreamId::from), from as usize);
+        // 取りこぼしを防ぐため、保存済みのペイロードを読む前に購読を開始する
+        let live = BroadcastStream::new(self.appended.subscribe());
+
+        let (stored, last_sequence) = match &stream {
+            Some(stream) => {
+                let stored = self.store.read_to_latest(stream, from).await?;
+                let last_sequence = stored.last().map(|payload| payload.sequence);
+                (stored.into_iter().collect::<Vec<_>>(), last_sequence)
+            }
+            None => (Vec::new(), None),
+        };
+
+        let live = live.filter_map(move |payload| {
+            let item = match payload {
+                Ok(payload) => match &stream {
+                    Some(stream) if payload.aggregate_type != stream.aggregate_type || payload.id != stream.id => None,
+                    // 保存済みのペイロードがない場合も、from より前のペイロードは送らない
+                    Some(_)
+                        if payload.sequence < from || last_sequence.is_some_and(|last| payload.sequence <= last) =>
+                    {
+                        None
+                    }
+                    _ => Some(Ok(payload.into())),
+                },
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => Some(Err(Status::data_loss(format!(
+                    "subscriber lagged behind by {skipped} payloads"
+                )))),
+            };
+            async move { item }
+        });
+        let stored = stream::iter(stored.into_iter().map(proto::Payload::from).map(Ok));
+        Ok(Response::new(Box::pin(stored.chain(live))))
+    }
+}
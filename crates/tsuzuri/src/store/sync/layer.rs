@@ -0,0 +1,266 @@
+//! Composable decorators for stores.
+//!
+//! A [`Layer`] wraps a store in another store that adds behaviour around every
+//! [`Reader`](crate::store::sync::reader::Reader) and
+//! [`Writer`](crate::store::sync::writer::Writer) call, in the same spirit as
+//! tower's `Layer`. Layers are stacked with [`StoreBuilder::layer`] when
+//! building an [`EventStore`](crate::store::sync::event_store::EventStore) or a
+//! [`QueryStore`](crate::store::sync::query_store::QueryStore).
+//!
+//! ```ignore
+//! let event_store = EventStore::builder(MemoryStore::new())
+//!     .layer(LoggingLayer::new("event_store"))
+//!     .layer(RetryLayer::new(3))
+//!     .layer(TimeoutLayer::new(Duration::from_secs(1)))
+//!     .build();
+//! ```
+//!
+//! The first layer added is the outermost one, so in the example above every
+//! call is logged once while the retries happen inside it.
+
+use std::marker::PhantomData;
+
+pub mod cache;
+pub mod logging;
+pub mod retry;
+pub mod timeout;
+
+pub use cache::{CacheLayer, Cached};
+pub use logging::{Logging, LoggingLayer};
+pub use retry::{Retry, RetryLayer};
+pub use timeout::{Timeout, TimeoutLayer};
+
+/// Decorates a store with additional behaviour.
+pub trait Layer<S> {
+    /// The store produced by wrapping `S`.
+    type Store;
+
+    /// Wraps the given store.
+    fn layer(&self, inner: S) -> Self::Store;
+}
+
+/// A layer that returns the store unchanged.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl<S> Layer<S> for Identity {
+    type Store = S;
+
+    fn layer(&self, inner: S) -> Self::Store {
+        inner
+    }
+}
+
+/// Two layers applied one after the other, `outer` wrapping the result of `inner`.
+#[derive(Clone, Debug)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    pub fn new(inner: Inner, outer: Outer) -> Self {
+        Self { inner, outer }
+    }
+}
+
+impl<S, Inner, Outer> Layer<S> for Stack<Inner, Outer>
+where
+    Inner: Layer<S>,
+    Outer: Layer<Inner::Store>,
+{
+    type Store = Outer::Store;
+
+    fn layer(&self, inner: S) -> Self::Store {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+/// Builds a store of type `T` from a base store and a stack of layers.
+///
+/// Obtained from [`EventStore::builder`](crate::store::sync::event_store::EventStore::builder)
+/// or [`QueryStore::builder`](crate::store::sync::query_store::QueryStore::builder).
+pub struct StoreBuilder<S, T, L = Identity> {
+    store: S,
+    layer: L,
+    _target: PhantomData<fn() -> T>,
+}
+
+impl<S, T> StoreBuilder<S, T> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            layer: Identity,
+            _target: PhantomData,
+        }
+    }
+}
+
+impl<S, T, L> StoreBuilder<S, T, L> {
+    /// Adds a layer inside the layers added so far.
+    pub fn layer<N>(self, layer: N) -> StoreBuilder<S, T, Stack<N, L>> {
+        StoreBuilder {
+            store: self.store,
+            layer: Stack::new(layer, self.layer),
+            _target: PhantomData,
+        }
+    }
+
+    /// Returns the base store wrapped in every layer.
+    pub fn into_store(self) -> L::Store
+    where
+        L: Layer<S>,
+    {
+        self.layer.layer(self.store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        payload::Payload,
+        stream::StreamId,
+        sync::{
+            error::StoreError, event_store::EventStore, memory_store::MemoryStore, query_store::QueryStore,
+            reader::Reader, writer::Writer,
+        },
+    };
+    use async_trait::async_trait;
+    use std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    /// Records its name on every read before delegating.
+    struct RecordingLayer {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl<S> Layer<S> for RecordingLayer {
+        type Store = Recording<S>;
+
+        fn layer(&self, inner: S) -> Self::Store {
+            Recording {
+                inner,
+                name: self.name,
+                calls: Arc::clone(&self.calls),
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Recording<S> {
+        inner: S,
+        name: &'static str,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl<S: Reader> Reader for Recording<S> {
+        async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+            self.calls.lock().unwrap().push(self.name);
+            self.inner.read(stream, seq).await
+        }
+
+        async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+            self.calls.lock().unwrap().push(self.name);
+            self.inner.read_to(stream, from, to).await
+        }
+    }
+
+    /// Delays the first `slow` reads by a second.
+    #[derive(Clone)]
+    struct Slow {
+        slow: Arc<AtomicUsize>,
+        attempts: Arc<AtomicUsize>,
+        store: MemoryStore,
+    }
+
+    #[async_trait]
+    impl Reader for Slow {
+        async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            if self
+                .slow
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            self.store.read(stream, seq).await
+        }
+
+        async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+            self.store.read_to(stream, from, to).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first_layer_is_outermost() -> Result<(), StoreError> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let layer = |name| RecordingLayer {
+            name,
+            calls: Arc::clone(&calls),
+        };
+        let store = EventStore::builder(MemoryStore::new())
+            .layer(layer("outer"))
+            .layer(layer("inner"))
+            .into_store();
+
+        store.read_to(&StreamId::new("Layer", "layer_1"), 0, 1).await?;
+        assert_eq!(*calls.lock().unwrap(), ["outer", "inner"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_timeout() -> Result<(), StoreError> {
+        let slow = Slow {
+            slow: Arc::new(AtomicUsize::new(1)),
+            attempts: Arc::new(AtomicUsize::new(0)),
+            store: MemoryStore::new(),
+        };
+        let stream = StreamId::new("Layer", "layer_2");
+        let payload = Payload::new(&stream, 1, vec![], None).unwrap();
+        slow.store.write(&stream, payload.clone()).await?;
+
+        // タイムアウトは一時的な失敗として再試行される
+        let store = EventStore::builder(slow.clone())
+            .layer(RetryLayer::new(1).initial_backoff(Duration::from_millis(1)))
+            .layer(TimeoutLayer::new(Duration::from_millis(20)))
+            .into_store();
+        assert_eq!(store.read(&stream, 1).await?, payload);
+        assert_eq!(slow.attempts.load(Ordering::SeqCst), 2);
+
+        // 再試行がなければタイムアウトがそのまま返る
+        slow.slow.store(1, Ordering::SeqCst);
+        let store = TimeoutLayer::new(Duration::from_millis(20)).layer(slow);
+        assert!(matches!(store.read(&stream, 1).await, Err(StoreError::Read(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builders_read_and_write() -> Result<(), StoreError> {
+        let event_store = EventStore::builder(MemoryStore::new())
+            .layer(LoggingLayer::new("event_store"))
+            .layer(RetryLayer::new(2))
+            .layer(TimeoutLayer::new(Duration::from_secs(1)))
+            .build();
+        let stream = StreamId::new("Layer", "layer_3");
+        let payload = Payload::new(&stream, 1, vec![1], None).unwrap();
+        event_store.write_store.write(&stream, payload.clone()).await?;
+        assert_eq!(event_store.read_store.read(&stream, 1).await?, payload);
+
+        let query_store = QueryStore::builder(MemoryStore::new())
+            .layer(CacheLayer::new(16))
+            .build();
+        query_store.write_store.write(&stream, payload.clone()).await?;
+        assert_eq!(query_store.read_store.read_to_latest(&stream, 0).await?.len(), 1);
+        Ok(())
+    }
+}
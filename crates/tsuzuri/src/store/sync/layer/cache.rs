@@ -0,0 +1,107 @@
+use crate::{
+    metrics,
+    store::{
+        page::{Page, ReadOptions},
+        payload::Payload,
+        stream::StreamId,
+        sync::{error::StoreError, layer::Layer, reader::Reader, writer::Writer},
+    },
+};
+use async_trait::async_trait;
+use moka::future::Cache;
+use std::collections::BTreeSet;
+
+/// Caches payloads by stream and sequence.
+///
+/// Stored payloads never change, so entries are only evicted to respect the
+/// capacity. Written payloads are added to the cache as well.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheLayer {
+    capacity: u64,
+}
+
+impl CacheLayer {
+    /// Keeps at most `capacity` payloads in memory.
+    pub fn new(capacity: u64) -> Self {
+        Self { capacity }
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Store = Cached<S>;
+
+    fn layer(&self, inner: S) -> Self::Store {
+        Cached {
+            inner,
+            cache: Cache::new(self.capacity),
+        }
+    }
+}
+
+/// Store produced by [`CacheLayer`].
+#[derive(Clone, Debug)]
+pub struct Cached<S> {
+    inner: S,
+    cache: Cache<(StreamId, usize), Payload>,
+}
+
+#[async_trait]
+impl<S> Reader for Cached<S>
+where
+    S: Reader,
+{
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        let key = (stream.clone(), seq);
+        if let Some(payload) = self.cache.get(&key).await {
+            metrics::cache(true);
+            return Ok(payload);
+        }
+        metrics::cache(false);
+        let payload = self.inner.read(stream, seq).await?;
+        self.cache.insert(key, payload.clone()).await;
+        Ok(payload)
+    }
+
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.inner.read_to(stream, from, to).await
+    }
+
+    async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.inner.read_to_latest(stream, from).await
+    }
+
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        self.inner.read_page(stream, options).await
+    }
+
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        self.inner.read_all_page(aggregate_type, options).await
+    }
+
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        self.inner.read_all().await
+    }
+
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        self.inner.read_category(aggregate_type).await
+    }
+}
+
+#[async_trait]
+impl<S> Writer for Cached<S>
+where
+    S: Writer,
+{
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        self.inner.append(stream, expected_version, payloads.clone()).await?;
+        for payload in payloads {
+            self.cache.insert((stream.clone(), payload.sequence), payload).await;
+        }
+        Ok(())
+    }
+}
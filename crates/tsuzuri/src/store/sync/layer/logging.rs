@@ -0,0 +1,120 @@
+use crate::store::{
+    page::{Page, ReadOptions},
+    payload::Payload,
+    stream::StreamId,
+    sync::{error::StoreError, layer::Layer, reader::Reader, writer::Writer},
+};
+use async_trait::async_trait;
+use std::{collections::BTreeSet, fmt::Display, future::Future, time::Instant};
+
+/// Logs every read and write with its duration through `tracing`.
+///
+/// Successful calls are logged at `DEBUG`, failed calls at `WARN`.
+#[derive(Clone, Debug)]
+pub struct LoggingLayer {
+    name: &'static str,
+}
+
+impl LoggingLayer {
+    /// `name` is attached to every log line to tell stores apart.
+    pub fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+}
+
+impl<S> Layer<S> for LoggingLayer {
+    type Store = Logging<S>;
+
+    fn layer(&self, inner: S) -> Self::Store {
+        Logging { inner, name: self.name }
+    }
+}
+
+/// Store produced by [`LoggingLayer`].
+#[derive(Clone, Debug)]
+pub struct Logging<S> {
+    inner: S,
+    name: &'static str,
+}
+
+impl<S> Logging<S> {
+    async fn log<T>(
+        &self,
+        operation: &'static str,
+        stream: impl Display,
+        fut: impl Future<Output = Result<T, StoreError>>,
+    ) -> Result<T, StoreError> {
+        let started = Instant::now();
+        let result = fut.await;
+        let elapsed = started.elapsed();
+        match &result {
+            Ok(_) => tracing::debug!(store = self.name, operation, %stream, ?elapsed, "store operation succeeded"),
+            Err(err) => {
+                tracing::warn!(store = self.name, operation, %stream, ?elapsed, error = %err, "store operation failed")
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<S> Reader for Logging<S>
+where
+    S: Reader,
+{
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        self.log("read", stream, self.inner.read(stream, seq)).await
+    }
+
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.log("read_to", stream, self.inner.read_to(stream, from, to)).await
+    }
+
+    async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.log("read_to_latest", stream, self.inner.read_to_latest(stream, from))
+            .await
+    }
+
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        self.log("read_page", stream, self.inner.read_page(stream, options))
+            .await
+    }
+
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        self.log(
+            "read_all_page",
+            aggregate_type.map_or_else(|| "*".to_string(), |aggregate_type| format!("{aggregate_type}-*")),
+            self.inner.read_all_page(aggregate_type, options),
+        )
+        .await
+    }
+
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        self.log("read_all", "*", self.inner.read_all()).await
+    }
+
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        self.log(
+            "read_category",
+            format!("{aggregate_type}-*"),
+            self.inner.read_category(aggregate_type),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<S> Writer for Logging<S>
+where
+    S: Writer,
+{
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        self.log("append", stream, self.inner.append(stream, expected_version, payloads))
+            .await
+    }
+}
@@ -0,0 +1,260 @@
+use crate::store::{
+    page::{Page, ReadOptions},
+    payload::Payload,
+    stream::StreamId,
+    sync::{
+        error::{is_transient_kind, StoreError},
+        layer::Layer,
+        reader::Reader,
+        writer::Writer,
+    },
+};
+use async_trait::async_trait;
+use std::{collections::BTreeSet, error::Error, future::Future, io, time::Duration};
+
+/// Retries reads and writes failing with a transient error, e.g. a timeout or a
+/// reset connection, with exponential backoff.
+///
+/// Writes are only retried through [`Writer::append`], which stores the same
+/// payloads at most once, so a write whose response was lost is not duplicated.
+#[derive(Clone, Debug)]
+pub struct RetryLayer {
+    policy: RetryPolicy,
+}
+
+impl RetryLayer {
+    /// Retries up to `max_retries` times, starting with a 50ms backoff.
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            policy: RetryPolicy {
+                max_retries,
+                initial_backoff: Duration::from_millis(50),
+                max_backoff: Duration::from_secs(5),
+            },
+        }
+    }
+
+    /// Sets the delay before the first retry. The delay doubles on every following attempt.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.policy.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the upper bound for the delay between two attempts.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.policy.max_backoff = backoff;
+        self
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Store = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Store {
+        Retry {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RetryPolicy {
+    max_retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    async fn run<T, F, Fut>(&self, mut f: F) -> Result<T, StoreError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, StoreError>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Err(err) if attempt < self.max_retries && is_retryable(&err) => {
+                    attempt += 1;
+                    tracing::debug!(attempt, error = %err, "retrying store operation");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// 存在しないデータや未対応の操作は再試行しても回復しないため、一時的な失敗のみ再試行する
+fn is_retryable(err: &StoreError) -> bool {
+    matches!(err, StoreError::Read(_) | StoreError::Write(_)) && err.sources().any(is_transient)
+}
+
+fn is_transient(err: &(dyn Error + 'static)) -> bool {
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return is_transient_kind(err.kind());
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(status) = err.downcast_ref::<tonic::Status>() {
+        return matches!(status.code(), tonic::Code::Unavailable | tonic::Code::DeadlineExceeded);
+    }
+    err.is::<tokio::time::error::Elapsed>()
+}
+
+/// Store produced by [`RetryLayer`].
+#[derive(Clone, Debug)]
+pub struct Retry<S> {
+    inner: S,
+    policy: RetryPolicy,
+}
+
+#[async_trait]
+impl<S> Reader for Retry<S>
+where
+    S: Reader,
+{
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        self.policy.run(|| self.inner.read(stream, seq)).await
+    }
+
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.policy.run(|| self.inner.read_to(stream, from, to)).await
+    }
+
+    async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.policy.run(|| self.inner.read_to_latest(stream, from)).await
+    }
+
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        self.policy.run(|| self.inner.read_page(stream, options)).await
+    }
+
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        self.policy
+            .run(|| self.inner.read_all_page(aggregate_type, options))
+            .await
+    }
+
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        self.policy.run(|| self.inner.read_all()).await
+    }
+
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        self.policy.run(|| self.inner.read_category(aggregate_type)).await
+    }
+}
+
+#[async_trait]
+impl<S> Writer for Retry<S>
+where
+    S: Writer,
+{
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        self.policy
+            .run(|| self.inner.append(stream, expected_version, payloads.clone()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::sync::memory_store::MemoryStore;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Fails the first `failures` writes with `kind`, after storing the payloads
+    /// when `lost` is set, as if the response was lost.
+    #[derive(Clone)]
+    struct Flaky {
+        failures: Arc<AtomicUsize>,
+        kind: io::ErrorKind,
+        lost: bool,
+        store: MemoryStore,
+    }
+
+    #[async_trait]
+    impl Writer for Flaky {
+        async fn append(
+            &self,
+            stream: &StreamId,
+            expected_version: usize,
+            payloads: Vec<Payload>,
+        ) -> Result<(), StoreError> {
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                if self.lost {
+                    self.store.append(stream, expected_version, payloads).await?;
+                }
+                return Err(StoreError::Write(Box::new(io::Error::from(self.kind))));
+            }
+            self.store.append(stream, expected_version, payloads).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let store = MemoryStore::new();
+        let flaky = Flaky {
+            failures: Arc::new(AtomicUsize::new(2)),
+            kind: io::ErrorKind::TimedOut,
+            lost: false,
+            store: store.clone(),
+        };
+        let retry = RetryLayer::new(2)
+            .initial_backoff(Duration::from_millis(1))
+            .layer(flaky.clone());
+
+        let stream = StreamId::new("Retry", "a");
+        let payload = Payload::new(&stream, 1, vec![], None).unwrap();
+        retry.write(&stream, payload.clone()).await.unwrap();
+        assert_eq!(store.read(&stream, 1).await.unwrap(), payload);
+
+        flaky.failures.store(3, Ordering::SeqCst);
+        let payload = Payload::new(&stream, 2, vec![], None).unwrap();
+        assert!(retry.write(&stream, payload).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_only_transient_errors() {
+        let store = MemoryStore::new();
+        let stream = StreamId::new("Retry", "b");
+        let layer = RetryLayer::new(2).initial_backoff(Duration::from_millis(1));
+
+        let flaky = Flaky {
+            failures: Arc::new(AtomicUsize::new(1)),
+            kind: io::ErrorKind::NotFound,
+            lost: false,
+            store: store.clone(),
+        };
+        let payload = Payload::new(&stream, 1, vec![], None).unwrap();
+        assert!(layer
+            .layer(flaky.clone())
+            .write(&stream, payload.clone())
+            .await
+            .is_err());
+        assert_eq!(flaky.failures.load(Ordering::SeqCst), 0);
+
+        // 保存後に応答が失われても、同じペイロードの再送は成功する
+        let flaky = Flaky {
+            failures: Arc::new(AtomicUsize::new(1)),
+            kind: io::ErrorKind::ConnectionReset,
+            lost: true,
+            store: store.clone(),
+        };
+        layer.layer(flaky).write(&stream, payload.clone()).await.unwrap();
+        assert_eq!(store.read_to_latest(&stream, 0).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::store::{
+    page::{Page, ReadOptions},
+    payload::Payload,
+    stream::StreamId,
+    sync::{error::StoreError, layer::Layer, reader::Reader, writer::Writer},
+};
+use async_trait::async_trait;
+use std::{collections::BTreeSet, time::Duration};
+
+/// Fails reads and writes that take longer than the given duration.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Store = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Store {
+        Timeout {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// Store produced by [`TimeoutLayer`].
+#[derive(Clone, Debug)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl<S> Reader for Timeout<S>
+where
+    S: Reader,
+{
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read(stream, seq))
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read_to(stream, from, to))
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+
+    async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read_to_latest(stream, from))
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read_page(stream, options))
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read_all_page(aggregate_type, options))
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read_all())
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.read_category(aggregate_type))
+            .await
+            .map_err(|elapsed| StoreError::Read(Box::new(elapsed)))?
+    }
+}
+
+#[async_trait]
+impl<S> Writer for Timeout<S>
+where
+    S: Writer,
+{
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        tokio::time::timeout(self.timeout, self.inner.append(stream, expected_version, payloads))
+            .await
+            .map_err(|elapsed| StoreError::Write(Box::new(elapsed)))?
+    }
+}
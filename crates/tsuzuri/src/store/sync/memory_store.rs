@@ -1,80 +1,289 @@
 use crate::store::{
+    page::{Direction, Page, ReadOptions},
     payload::Payload,
+    stream::StreamId,
     sync::{error::StoreError, reader::Reader, writer::Writer},
 };
 use async_trait::async_trait;
 use std::io;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Bound,
     sync::Arc,
 };
 use tokio::sync::RwLock;
 
-/// A simple in-memory store that keeps payloads organized by `id` and sequence number.
+/// A simple in-memory store that keeps payloads organized by stream and sequence number.
+///
+/// Every read of all streams, e.g. [`Reader::read_all`] and [`Reader::read_all_page`],
+/// returns the payloads in the order they were appended in.
 #[derive(Clone, Debug, Default)]
 pub struct MemoryStore {
-    // The inner store maps an `id` (String) to a BTreeMap where the key is the sequence number.
-    store: Arc<RwLock<HashMap<String, BTreeMap<usize, Payload>>>>,
+    store: Arc<RwLock<Streams>>,
+}
+
+#[derive(Debug, Default)]
+struct Streams {
+    // ストリームごとに、シーケンス番号をキーとしてペイロードを保持する
+    streams: HashMap<StreamId, BTreeMap<usize, Payload>>,
+    // 追記された順のペイロード。インデックス + 1 がグローバルな位置になる
+    log: Vec<(StreamId, usize)>,
 }
 
 impl MemoryStore {
     /// Create a new empty MemoryStore.
     pub fn new() -> Self {
-        MemoryStore {
-            store: Arc::new(RwLock::new(HashMap::new())),
-        }
+        Self::default()
     }
 
-    /// Append a new payload to the store.
-    ///
-    /// Returns an error if a payload with the same sequence number already exists for the given id.
-    pub async fn append(&self, id: &str, payload: Payload) -> Result<(), StoreError> {
-        let mut store = self.store.write().await;
-        let entry = store.entry(id.to_string()).or_insert_with(BTreeMap::new);
-        if entry.contains_key(&payload.sequence) {
-            return Err(StoreError::Write(Box::new(io::Error::new(
-                io::ErrorKind::AlreadyExists,
-                format!(
-                    "Payload with sequence {} already exists for id {}",
-                    payload.sequence, id
-                ),
-            ))));
-        }
-        entry.insert(payload.sequence, payload);
-        Ok(())
+    // read_all_page と同じく、追記された順に読む
+    async fn read_streams(&self, filter: impl Fn(&StreamId) -> bool) -> Vec<Payload> {
+        let store = self.store.read().await;
+        store
+            .log
+            .iter()
+            .filter(|(stream, _)| filter(stream))
+            .map(|(stream, sequence)| store.streams[stream][sequence].clone())
+            .collect()
     }
 }
 
 #[async_trait]
 impl Reader for MemoryStore {
-    async fn read(&self, id: &str, seq: usize) -> Result<Payload, StoreError> {
-        let store = self.store.read().await;
-        if let Some(map) = store.get(id) {
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        let store = &self.store.read().await.streams;
+        if let Some(map) = store.get(stream) {
             if let Some(payload) = map.get(&seq) {
                 return Ok(payload.clone());
             }
         }
         Err(StoreError::Read(Box::new(io::Error::new(
             io::ErrorKind::NotFound,
-            format!("Payload not found for id: {} and sequence: {}", id, seq),
+            format!("Payload not found for stream: {} and sequence: {}", stream, seq),
         ))))
     }
 
-    async fn read_to(&self, id: &str, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
-        let store = self.store.read().await;
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        let store = &self.store.read().await.streams;
         // 存在しない場合は空のBTreeSetを返す
-        let set: BTreeSet<_> = if let Some(map) = store.get(id) {
+        let set: BTreeSet<_> = if let Some(map) = store.get(stream) {
             map.range(from..to).map(|(_seq, payload)| payload.clone()).collect()
         } else {
             BTreeSet::new()
         };
         Ok(set)
     }
+
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        let store = &self.store.read().await.streams;
+        let Some(map) = store.get(stream) else {
+            return Ok(Page::default());
+        };
+        let range = match (options.direction, options.cursor) {
+            (Direction::Forward, cursor) => (Bound::Included(cursor.unwrap_or(0)), Bound::Unbounded),
+            (Direction::Backward, Some(cursor)) => (Bound::Unbounded, Bound::Included(cursor)),
+            (Direction::Backward, None) => (Bound::Unbounded, Bound::Unbounded),
+        };
+        // ページに含まれるペイロードだけを複製する
+        Ok(options.paginate(map.range(range).map(|(_seq, payload)| payload.clone())))
+    }
+
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        let store = self.store.read().await;
+        let len = store.log.len();
+        let range = match options.direction {
+            Direction::Forward => options.cursor.unwrap_or(1).saturating_sub(1).min(len)..len,
+            Direction::Backward => 0..options.cursor.unwrap_or(len).min(len),
+        };
+        let start = range.start;
+        let payloads = store.log[range]
+            .iter()
+            .enumerate()
+            .filter(|(_, (stream, _))| {
+                aggregate_type.is_none_or(|aggregate_type| stream.aggregate_type == aggregate_type)
+            })
+            .map(|(index, (stream, sequence))| (start + index + 1, store.streams[stream][sequence].clone()));
+        Ok(options.paginate_by(payloads))
+    }
+
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        Ok(self.read_streams(|_| true).await)
+    }
+
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        Ok(self
+            .read_streams(|stream| stream.aggregate_type == aggregate_type)
+            .await)
+    }
 }
 
 #[async_trait]
 impl Writer for MemoryStore {
-    async fn write(&self, id: &str, payload: Payload) -> Result<(), StoreError> {
-        self.append(id, payload).await
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        for (sequence, payload) in (expected_version + 1..).zip(&payloads) {
+            if payload.sequence != sequence || payload.stream_id() != *stream {
+                return Err(StoreError::Write(Box::new(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "expected payload {sequence} of stream {stream}, got payload {} of stream {}",
+                        payload.sequence,
+                        payload.stream_id()
+                    ),
+                ))));
+            }
+        }
+
+        let mut store = self.store.write().await;
+        let Streams { streams, log } = &mut *store;
+        let entry = streams.entry(stream.clone()).or_default();
+        // 同じペイロードが保存済みなら、応答を失った書き込みの再送として成功させる。
+        // 空の追記はバージョンの確認だけを行う
+        let stored = !payloads.is_empty()
+            && payloads.iter().all(|payload| {
+                entry
+                    .get(&payload.sequence)
+                    .is_some_and(|stored| stored.event_id == payload.event_id)
+            });
+        if stored {
+            return Ok(());
+        }
+        let version = entry.last_key_value().map_or(0, |(sequence, _)| *sequence);
+        if version != expected_version {
+            return Err(StoreError::Conflict {
+                stream: stream.clone(),
+                sequence: expected_version + 1,
+            });
+        }
+        for payload in payloads {
+            log.push((stream.clone(), payload.sequence));
+            entry.insert(payload.sequence, payload);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_append_is_idempotent() -> Result<(), StoreError> {
+        let store = MemoryStore::new();
+        let stream = StreamId::new("Memory", "memory_1");
+        let payload = Payload::new(&stream, 1, vec![1], None).unwrap();
+
+        store.write(&stream, payload.clone()).await?;
+        store.write(&stream, payload.clone()).await?;
+        assert_eq!(store.read_to_latest(&stream, 0).await?.len(), 1);
+
+        let other = Payload::new(&stream, 1, vec![2], None).unwrap();
+        assert!(matches!(
+            store.write(&stream, other).await,
+            Err(StoreError::Conflict { sequence: 1, .. })
+        ));
+        assert_eq!(store.read(&stream, 1).await?, payload);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_global_reads_use_append_order() -> Result<(), StoreError> {
+        use crate::{
+            clock::{self, Clock, ManualClock},
+            store::page::ReadOptions,
+        };
+        use time::OffsetDateTime;
+
+        let store = MemoryStore::new();
+        let (a, b) = (
+            StreamId::new("Memory", "memory_5_A"),
+            StreamId::new("Memory", "memory_5_B"),
+        );
+        // 時計を止めても、書き込んだ順に読める
+        let clock: Arc<dyn Clock> = Arc::new(ManualClock::new(OffsetDateTime::UNIX_EPOCH));
+        for (stream, sequence) in [(&b, 1), (&a, 1), (&b, 2)] {
+            let payload = clock::scope(&clock, || Payload::new(stream, sequence, vec![], None)).unwrap();
+            store.write(stream, payload).await?;
+        }
+
+        let order = |payloads: Vec<Payload>| {
+            payloads
+                .into_iter()
+                .map(|payload| (payload.id, payload.sequence))
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            ("memory_5_B".to_string(), 1),
+            ("memory_5_A".to_string(), 1),
+            ("memory_5_B".to_string(), 2),
+        ];
+        assert_eq!(order(store.read_all().await?), expected);
+        assert_eq!(order(store.read_category("Memory").await?), expected);
+        let page = store.read_all_page(None, &ReadOptions::forward()).await?;
+        assert_eq!(order(page.payloads), expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_append_is_atomic() -> Result<(), StoreError> {
+        let store = MemoryStore::new();
+        let stream = StreamId::new("Memory", "memory_4");
+        let payload = |sequence| Payload::new(&stream, sequence, vec![], None).unwrap();
+        let batch = vec![payload(1), payload(2)];
+        store.append(&stream, 0, batch.clone()).await?;
+        // 再送されたバッチは保存済みとして成功する
+        store.append(&stream, 0, batch).await?;
+
+        // 古いバージョンへの追記は、どのペイロードも保存しない
+        assert!(matches!(
+            store.append(&stream, 1, vec![payload(2), payload(3)]).await,
+            Err(StoreError::Conflict { sequence: 2, .. })
+        ));
+        assert!(matches!(
+            store.append(&stream, 2, vec![payload(3), payload(5)]).await,
+            Err(StoreError::Write(_))
+        ));
+        assert_eq!(store.read_to_latest(&stream, 0).await?.len(), 2);
+
+        // 空の追記も期待するバージョンを確認する
+        store.append(&stream, 2, vec![]).await?;
+        assert!(matches!(
+            store.append(&stream, 1, vec![]).await,
+            Err(StoreError::Conflict { sequence: 2, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_page_backward() -> Result<(), StoreError> {
+        let store = MemoryStore::new();
+        let stream = StreamId::new("Memory", "memory_2");
+        for sequence in 1..=5 {
+            store
+                .write(&stream, Payload::new(&stream, sequence, vec![], None).unwrap())
+                .await?;
+        }
+        let sequences = |page: &Page| page.payloads.iter().map(|payload| payload.sequence).collect::<Vec<_>>();
+
+        // 最新の2件から順に遡る
+        let options = ReadOptions::backward().max_count(2);
+        let page = store.read_page(&stream, &options).await?;
+        assert_eq!((sequences(&page), page.next), (vec![5, 4], Some(3)));
+        let page = store.read_page(&stream, &options.cursor(3)).await?;
+        assert_eq!((sequences(&page), page.next), (vec![3, 2], Some(1)));
+        let page = store.read_page(&stream, &options.cursor(1)).await?;
+        assert_eq!((sequences(&page), page.next), (vec![1], None));
+
+        let missing = StreamId::new("Memory", "memory_3");
+        assert_eq!(store.read_page(&missing, &options).await?, Page::default());
+
+        Ok(())
     }
 }
@@ -1,4 +1,5 @@
 use crate::store::sync::{
+    layer::{Layer, StoreBuilder},
     reader::{DefaultQuery, ReadStore, Reader},
     writer::{WriteStore, Writer},
 };
@@ -14,8 +15,23 @@ impl QueryStore {
         S: Reader + Writer + Clone + 'static,
     {
         QueryStore {
-            read_store: ReadStore::new(store.clone(), DefaultQuery {}),
-            write_store: WriteStore::new(store),
+            read_store: ReadStore::new(store.clone(), DefaultQuery {}).labeled("query"),
+            write_store: WriteStore::new(store).labeled("query"),
         }
     }
+
+    /// Starts building a QueryStore whose store is wrapped in layers.
+    pub fn builder<S>(store: S) -> StoreBuilder<S, QueryStore> {
+        StoreBuilder::new(store)
+    }
+}
+
+impl<S, L> StoreBuilder<S, QueryStore, L>
+where
+    L: Layer<S>,
+    L::Store: Reader + Writer + Clone + 'static,
+{
+    pub fn build(self) -> QueryStore {
+        QueryStore::new(self.into_store())
+    }
 }
@@ -1,13 +1,88 @@
-use crate::store::{payload::Payload, sync::error::StoreError};
+use crate::{
+    metrics,
+    store::{
+        future,
+        page::{Direction, Page, ReadOptions},
+        payload::Payload,
+        stream::StreamId,
+        sync::error::StoreError,
+    },
+};
 use async_trait::async_trait;
-use std::{collections::BTreeSet, fmt::Debug, sync::Arc};
+use futures_util::Stream;
+use std::{collections::BTreeSet, fmt::Debug, io, sync::Arc, time::Instant};
+use tracing::{field, Span};
 
 #[async_trait]
 pub trait Reader: 'static + Send + Sync {
-    async fn read(&self, id: &str, seq: usize) -> Result<Payload, StoreError>;
-    async fn read_to(&self, id: &str, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError>;
-    async fn read_to_latest(&self, id: &str, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
-        self.read_to(id, from, usize::MAX).await
+    async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError>;
+    async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError>;
+    async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        self.read_to(stream, from, usize::MAX).await
+    }
+    /// Reads a page of the stream in the direction of `options`.
+    ///
+    /// The default implementation reads the page and the payload after it with
+    /// [`read_to`](Self::read_to), as sequences are contiguous. A backward read
+    /// without a cursor reads the whole stream, as its end is not known.
+    async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        // 次のページの有無を知るため、1件多く読む
+        let window = options
+            .max_count
+            .map_or(usize::MAX, |max_count| max_count.saturating_add(1));
+        let payloads = match options.direction {
+            Direction::Forward => {
+                // シーケンスは1から始まる
+                let from = options.cursor.unwrap_or(1);
+                self.read_to(stream, from, from.saturating_add(window)).await?
+            }
+            Direction::Backward => match options.cursor {
+                Some(cursor) => {
+                    let to = cursor.saturating_add(1);
+                    self.read_to(stream, to.saturating_sub(window), to).await?
+                }
+                None => self.read_to_latest(stream, 0).await?,
+            },
+        };
+        Ok(options.paginate(payloads))
+    }
+    /// Reads the payloads of every stream in their global order, the order of
+    /// [`read_all_page`](Self::read_all_page).
+    async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        Err(StoreError::Read(Box::new(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "reading all streams is not supported by this store",
+        ))))
+    }
+    /// Reads the payloads of every stream of an aggregate type in their global order.
+    async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        let mut payloads = self.read_all().await?;
+        payloads.retain(|payload| payload.aggregate_type == aggregate_type);
+        Ok(payloads)
+    }
+    /// Reads a page of every stream, or of the streams of `aggregate_type` when set.
+    ///
+    /// The cursor is a global position starting at 1, which orders the payloads
+    /// of every stream. The default implementation numbers the payloads of
+    /// [`read_all`](Self::read_all) or [`read_category`](Self::read_category) in
+    /// order, reading all of them before paginating, stores should override it
+    /// to read only the page.
+    async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        let payloads = match aggregate_type {
+            Some(aggregate_type) => self.read_category(aggregate_type).await?,
+            None => self.read_all().await?,
+        };
+        let (len, cursor) = (payloads.len(), options.cursor);
+        let range = match options.direction {
+            Direction::Forward => cursor.unwrap_or(1).saturating_sub(1).min(len)..len,
+            Direction::Backward => 0..cursor.unwrap_or(len).min(len),
+        };
+        let start = range.start;
+        let positioned = payloads
+            .into_iter()
+            .enumerate()
+            .map(|(index, payload)| (index + 1, payload));
+        Ok(options.paginate_by(positioned.skip(start).take(range.len())))
     }
 }
 
@@ -22,6 +97,9 @@ impl Query for DefaultQuery {}
 
 pub struct ReadStore {
     base: Arc<dyn Reader>,
+    // メトリクスの store ラベル ("event" または "query")
+    store: &'static str,
+    #[allow(dead_code)]
     queries: Vec<Box<dyn Query>>,
 }
 
@@ -35,6 +113,7 @@ impl Clone for ReadStore {
     fn clone(&self) -> Self {
         Self {
             base: Arc::clone(&self.base),
+            store: self.store,
             queries: vec![],
         }
     }
@@ -44,19 +123,124 @@ impl ReadStore {
     pub fn new(reader: impl Reader, queries: impl Query) -> Self {
         Self {
             base: Arc::new(reader),
+            store: "event",
             queries: vec![Box::new(queries)],
         }
     }
 
-    pub async fn read(&self, id: &str, seq: usize) -> Result<Payload, StoreError> {
-        self.base.read(id, seq).await
+    /// Sets the `store` label of the metrics recorded by this store.
+    pub(crate) fn labeled(mut self, store: &'static str) -> Self {
+        self.store = store;
+        self
+    }
+
+    #[tracing::instrument(name = "read", skip(self, stream), fields(%stream), err)]
+    pub async fn read(&self, stream: &StreamId, seq: usize) -> Result<Payload, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read(stream, seq).await;
+        metrics::store_operation(self.store, "read", &result, started.elapsed());
+        result
+    }
+
+    #[tracing::instrument(name = "read_to", skip(self, stream), fields(%stream, events = field::Empty), err)]
+    pub async fn read_to(&self, stream: &StreamId, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read_to(stream, from, to).await;
+        metrics::store_operation(self.store, "read_to", &result, started.elapsed());
+        let payloads = result?;
+        Span::current().record("events", payloads.len());
+        Ok(payloads)
+    }
+
+    #[tracing::instrument(name = "read_to_latest", skip(self, stream), fields(%stream, events = field::Empty), err)]
+    pub async fn read_to_latest(&self, stream: &StreamId, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read_to_latest(stream, from).await;
+        metrics::store_operation(self.store, "read_to_latest", &result, started.elapsed());
+        let payloads = result?;
+        Span::current().record("events", payloads.len());
+        Ok(payloads)
+    }
+
+    #[tracing::instrument(
+        name = "read_page",
+        skip(self, stream),
+        fields(%stream, direction = ?options.direction, events = field::Empty),
+        err
+    )]
+    pub async fn read_page(&self, stream: &StreamId, options: &ReadOptions) -> Result<Page, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read_page(stream, options).await;
+        metrics::store_operation(self.store, "read_page", &result, started.elapsed());
+        let page = result?;
+        Span::current().record("events", page.payloads.len());
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "read_all_page",
+        skip(self),
+        fields(direction = ?options.direction, events = field::Empty),
+        err
+    )]
+    pub async fn read_all_page(&self, aggregate_type: Option<&str>, options: &ReadOptions) -> Result<Page, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read_all_page(aggregate_type, options).await;
+        metrics::store_operation(self.store, "read_all_page", &result, started.elapsed());
+        let page = result?;
+        Span::current().record("events", page.payloads.len());
+        Ok(page)
+    }
+
+    /// Streams every payload of the stream page by page, see [`future::read_stream`].
+    ///
+    /// Each page is read with [`read_page`](Self::read_page), so it is traced and measured.
+    pub fn stream<'a>(
+        &'a self,
+        stream: &'a StreamId,
+        options: ReadOptions,
+    ) -> impl Stream<Item = Result<Payload, StoreError>> + Send + 'a {
+        future::paged(
+            options,
+            move |options| async move { self.read_page(stream, &options).await },
+        )
+    }
+
+    /// Streams the payloads of every stream page by page, see [`future::read_all`].
+    pub fn stream_all(&self, options: ReadOptions) -> impl Stream<Item = Result<Payload, StoreError>> + Send + '_ {
+        future::paged(options, move |options| async move {
+            self.read_all_page(None, &options).await
+        })
+    }
+
+    /// Streams the payloads of every stream of an aggregate type page by page, see [`future::read_category`].
+    pub fn stream_category<'a>(
+        &'a self,
+        aggregate_type: &'a str,
+        options: ReadOptions,
+    ) -> impl Stream<Item = Result<Payload, StoreError>> + Send + 'a {
+        future::paged(options, move |options| async move {
+            self.read_all_page(Some(aggregate_type), &options).await
+        })
     }
 
-    pub async fn read_to(&self, id: &str, from: usize, to: usize) -> Result<BTreeSet<Payload>, StoreError> {
-        self.base.read_to(id, from, to).await
+    #[tracing::instrument(name = "read_all", skip(self), fields(events = field::Empty), err)]
+    pub async fn read_all(&self) -> Result<Vec<Payload>, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read_all().await;
+        metrics::store_operation(self.store, "read_all", &result, started.elapsed());
+        let payloads = result?;
+        Span::current().record("events", payloads.len());
+        Ok(payloads)
     }
 
-    pub async fn read_to_latest(&self, id: &str, from: usize) -> Result<BTreeSet<Payload>, StoreError> {
-        self.base.read_to_latest(id, from).await
+    #[tracing::instrument(name = "read_category", skip(self), fields(events = field::Empty), err)]
+    pub async fn read_category(&self, aggregate_type: &str) -> Result<Vec<Payload>, StoreError> {
+        let started = Instant::now();
+        let result = self.base.read_category(aggregate_type).await;
+        metrics::store_operation(self.store, "read_category", &result, started.elapsed());
+        let payloads = result?;
+        Span::current().record("events", payloads.len());
+        Ok(payloads)
     }
 }
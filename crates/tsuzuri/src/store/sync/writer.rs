@@ -1,14 +1,35 @@
-use crate::store::{payload::Payload, sync::error::StoreError};
+use crate::{
+    metrics,
+    store::{payload::Payload, stream::StreamId, sync::error::StoreError},
+};
 use async_trait::async_trait;
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, sync::Arc, time::Instant};
 
 #[async_trait]
 pub trait Writer: 'static + Sync + Send {
-    async fn write(&self, id: &str, payload: Payload) -> Result<(), StoreError>;
+    /// Appends `payloads` to the stream whose latest sequence is `expected_version`.
+    ///
+    /// Either every payload is stored or none is. Fails with [`StoreError::Conflict`]
+    /// when the stream is not at `expected_version`, unless the same payloads are
+    /// already stored, e.g. by a retried request whose response was lost.
+    async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError>;
+
+    /// Appends a single payload after the previous sequence.
+    async fn write(&self, stream: &StreamId, payload: Payload) -> Result<(), StoreError> {
+        self.append(stream, payload.sequence.saturating_sub(1), vec![payload])
+            .await
+    }
 }
 
 pub struct WriteStore {
     base: Arc<dyn Writer>,
+    // メトリクスの store ラベル ("event" または "query")
+    store: &'static str,
 }
 
 impl Debug for WriteStore {
@@ -21,16 +42,48 @@ impl Clone for WriteStore {
     fn clone(&self) -> Self {
         Self {
             base: Arc::clone(&self.base),
+            store: self.store,
         }
     }
 }
 
 impl WriteStore {
     pub fn new(writer: impl Writer) -> Self {
-        Self { base: Arc::new(writer) }
+        Self {
+            base: Arc::new(writer),
+            store: "event",
+        }
+    }
+
+    /// Sets the `store` label of the metrics recorded by this store.
+    pub(crate) fn labeled(mut self, store: &'static str) -> Self {
+        self.store = store;
+        self
+    }
+
+    #[tracing::instrument(name = "write", skip(self, stream, payload), fields(%stream, sequence = payload.sequence), err)]
+    pub async fn write(&self, stream: &StreamId, payload: Payload) -> Result<(), StoreError> {
+        let started = Instant::now();
+        let result = self.base.write(stream, payload).await;
+        metrics::store_operation(self.store, "write", &result, started.elapsed());
+        result
     }
 
-    pub async fn write(&self, id: &str, payload: Payload) -> Result<(), StoreError> {
-        self.base.write(id, payload).await
+    #[tracing::instrument(
+        name = "append",
+        skip(self, stream, payloads),
+        fields(%stream, expected_version, events = payloads.len()),
+        err
+    )]
+    pub async fn append(
+        &self,
+        stream: &StreamId,
+        expected_version: usize,
+        payloads: Vec<Payload>,
+    ) -> Result<(), StoreError> {
+        let started = Instant::now();
+        let result = self.base.append(stream, expected_version, payloads).await;
+        metrics::store_operation(self.store, "append", &result, started.elapsed());
+        result
     }
 }
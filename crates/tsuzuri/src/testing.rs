@@ -0,0 +1,211 @@
+//! Given/When/Then tests for aggregates.
+//!
+//! Commands are handled by the aggregate's [`Handle`] and [`Apply`]
+//! implementations directly, without any store:
+//!
+//! ```ignore
+//! given::<BankAccount>([AccountOpened {}.into()])
+//!     .when(DepositFunds { amount: 100 })
+//!     .then_expect_events([FundsDeposited { amount: 100 }]);
+//! ```
+//!
+//! Events are compared through their JSON representation, and mismatches panic
+//! with a line diff of the expected and actual events.
+
+use crate::aggregate::{Aggregate, Apply, Handle, State};
+use serde::Serialize;
+use std::fmt::{Debug, Write};
+
+#[cfg(feature = "proptest")]
+pub mod proptest;
+
+type HandleResult<T> =
+    Result<Vec<<State<T> as Aggregate>::Event>, <State<T> as Handle<<T as Aggregate>::Command>>::Error>;
+
+/// Starts a test from the events previously stored for the aggregate.
+pub fn given<T>(events: impl IntoIterator<Item = T::Event>) -> Given<T>
+where
+    T: Aggregate,
+    State<T>: Apply<T::Event>,
+{
+    let mut state = State::<T>::init(String::new());
+    for event in events {
+        state.apply(event);
+    }
+    Given { state }
+}
+
+/// Starts a test from an aggregate without any stored event.
+pub fn given_no_previous_events<T>() -> Given<T>
+where
+    T: Aggregate,
+    State<T>: Apply<T::Event>,
+{
+    given::<T>([])
+}
+
+/// The aggregate rehydrated from the given events.
+pub struct Given<T> {
+    state: State<T>,
+}
+
+impl<T> Given<T>
+where
+    T: Aggregate,
+    State<T>: Handle<T::Command>,
+{
+    /// Handles the command against the aggregate.
+    pub fn when(self, cmd: impl Into<T::Command>) -> Then<T> {
+        Then {
+            result: self.state.handle(cmd.into()),
+        }
+    }
+}
+
+/// The outcome of the command.
+pub struct Then<T>
+where
+    T: Aggregate,
+    State<T>: Handle<T::Command>,
+{
+    result: HandleResult<T>,
+}
+
+impl<T> Then<T>
+where
+    T: Aggregate,
+    State<T>: Handle<T::Command>,
+{
+    /// Asserts that the command emitted exactly the expected events.
+    #[track_caller]
+    pub fn then_expect_events<E>(self, expected: impl IntoIterator<Item = E>)
+    where
+        E: Into<T::Event>,
+    {
+        let expected: Vec<T::Event> = expected.into_iter().map(Into::into).collect();
+        match self.result {
+            Ok(actual) => {
+                let (expected, actual) = (to_pretty_json(&expected), to_pretty_json(&actual));
+                if expected != actual {
+                    panic!("unexpected events\n{}", diff(&expected, &actual));
+                }
+            }
+            Err(err) => panic!("expected events but the command was rejected: {err:?}"),
+        }
+    }
+
+    /// Asserts that the command was rejected with an error formatting like `expected` with `Debug`.
+    #[track_caller]
+    pub fn then_expect_error<E: Debug>(self, expected: E) {
+        match self.result {
+            Ok(events) => panic!("expected an error but the command emitted events: {events:?}"),
+            Err(err) => {
+                let (expected, actual) = (format!("{expected:#?}"), format!("{err:#?}"));
+                if expected != actual {
+                    panic!("unexpected error\n{}", diff(&expected, &actual));
+                }
+            }
+        }
+    }
+
+    /// Asserts that the command was rejected with an error satisfying the predicate.
+    #[track_caller]
+    pub fn then_expect_error_matches(self, predicate: impl FnOnce(&<State<T> as Handle<T::Command>>::Error) -> bool) {
+        match self.result {
+            Ok(events) => panic!("expected an error but the command emitted events: {events:?}"),
+            Err(err) => assert!(predicate(&err), "unexpected error: {err:?}"),
+        }
+    }
+
+    /// Returns the result of the command for custom assertions.
+    pub fn inspect_result(self) -> HandleResult<T> {
+        self.result
+    }
+}
+
+fn to_pretty_json<T: Serialize + Debug>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| format!("{value:#?}"))
+}
+
+/// Formats a line diff, prefixing expected lines with `-` and actual lines with `+`.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+
+    // 最長共通部分列の長さを末尾から求める
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            let _ = writeln!(out, "  {}", expected[i]);
+            (i, j) = (i + 1, j + 1);
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "- {}", expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", actual[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{
+        AccountOpened, BankAccount, BankAccountCommand, BankAccountCommandError, BankAccountError, BankAccountEvent,
+        DepositFunds, FundsDeposited, FundsWithdrawn, OpenAccount, WithdrawFunds,
+    };
+
+    #[test]
+    fn test_given_when_then() {
+        given_no_previous_events::<BankAccount>()
+            .when(OpenAccount {})
+            .then_expect_events([AccountOpened {}]);
+
+        given::<BankAccount>([AccountOpened {}.into(), FundsDeposited { amount: 100 }.into()])
+            .when(WithdrawFunds { amount: 30 })
+            .then_expect_events([FundsWithdrawn { amount: 30 }]);
+
+        given::<BankAccount>([AccountOpened {}.into()])
+            .when(WithdrawFunds { amount: 30 })
+            .then_expect_error(BankAccountCommandError::WithdrawFunds(
+                BankAccountError::InsufficientBalance,
+            ));
+
+        given::<BankAccount>([AccountOpened {}.into()])
+            .when(BankAccountCommand::CloseAccount {
+                reason: "moved".to_string(),
+            })
+            .then_expect_events([BankAccountEvent::AccountClosed]);
+
+        given_no_previous_events::<BankAccount>()
+            .when(DepositFunds { amount: 10 })
+            .then_expect_error_matches(|err| {
+                matches!(
+                    err,
+                    BankAccountCommandError::DepositFunds(BankAccountError::AccountNotOpen)
+                )
+            });
+    }
+
+    #[test]
+    #[should_panic(expected = "-       \"amount\": 20\n+       \"amount\": 10")]
+    fn test_then_expect_events_mismatch() {
+        given::<BankAccount>([AccountOpened {}.into()])
+            .when(DepositFunds { amount: 10 })
+            .then_expect_events([FundsDeposited { amount: 20 }]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Property-based testing of aggregate invariants.
+//!
+//! Random command sequences are generated from a proptest strategy and handled
+//! by `State<T>`. Rejected commands are skipped, and the events of accepted
+//! commands are applied before every invariant is checked. When the sequence
+//! ends, the emitted events are replayed on a fresh aggregate, which must end
+//! up in the same state.
+//!
+//! ```ignore
+//! CommandSequence::<BankAccount, _>::new(command_strategy())
+//!     .invariant("balance never negative", |account| account.balance >= 0)
+//!     .run();
+//! ```
+//!
+//! Failing sequences are shrunk, and the minimal one is reported in the panic message.
+
+use crate::aggregate::{Aggregate, Apply, Handle, State};
+use ::proptest::{
+    collection,
+    strategy::Strategy,
+    test_runner::{Config, TestCaseError, TestError, TestRunner},
+};
+use std::fmt::Debug;
+
+type Invariant<T> = (String, Box<dyn Fn(&T) -> bool>);
+
+/// Runs random command sequences against an aggregate.
+pub struct CommandSequence<T, S> {
+    strategy: S,
+    max_len: usize,
+    config: Config,
+    invariants: Vec<Invariant<T>>,
+}
+
+impl<T, S> CommandSequence<T, S>
+where
+    T: Aggregate,
+    T::Command: Clone + Debug,
+    State<T>: Apply<<State<T> as Aggregate>::Event> + Handle<T::Command>,
+    S: Strategy<Value = T::Command>,
+{
+    /// Creates a runner generating commands from the given strategy.
+    pub fn new(strategy: S) -> Self {
+        Self {
+            strategy,
+            max_len: 32,
+            config: Config::default(),
+            invariants: Vec::new(),
+        }
+    }
+
+    /// Sets the maximum number of commands in a sequence. Defaults to 32.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Sets the proptest configuration, e.g. the number of cases.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Adds an invariant which must hold after every command.
+    pub fn invariant(mut self, name: impl Into<String>, check: impl Fn(&T) -> bool + 'static) -> Self {
+        self.invariants.push((name.into(), Box::new(check)));
+        self
+    }
+
+    /// Runs the sequences, panicking with the minimal failing sequence.
+    #[track_caller]
+    pub fn run(self) {
+        let mut runner = TestRunner::new(self.config.clone());
+        let strategy = collection::vec(self.strategy, 0..=self.max_len);
+        let result = runner.run(&strategy, |commands| check_sequence(&self.invariants, commands));
+
+        match result {
+            Ok(()) => {}
+            Err(TestError::Fail(reason, commands)) => {
+                panic!("{reason}\nminimal failing sequence: {commands:#?}")
+            }
+            Err(TestError::Abort(reason)) => panic!("aborted: {reason}"),
+        }
+    }
+}
+
+fn check_sequence<T>(invariants: &[Invariant<T>], commands: Vec<T::Command>) -> Result<(), TestCaseError>
+where
+    T: Aggregate,
+    T::Command: Clone + Debug,
+    State<T>: Apply<<State<T> as Aggregate>::Event> + Handle<T::Command>,
+{
+    let mut state = State::<T>::init(String::new());
+    let mut history = Vec::new();
+
+    for (step, cmd) in commands.into_iter().enumerate() {
+        // 拒否されたコマンドは状態を変えないので、そのまま次へ進む
+        let Ok(events) = state.handle(cmd.clone()) else {
+            continue;
+        };
+        for event in events {
+            state.apply(event.clone());
+            history.push(event);
+        }
+
+        for (name, check) in invariants {
+            if !check(&state.0) {
+                return Err(TestCaseError::fail(format!(
+                    "invariant `{name}` violated after command #{step} {cmd:?}\nstate: {:#?}",
+                    state.0
+                )));
+            }
+        }
+    }
+
+    let mut replayed = State::<T>::init(String::new());
+    for event in history {
+        replayed.apply(event);
+    }
+    let (expected, actual) = (format!("{:#?}", state.0), format!("{:#?}", replayed.0));
+    if expected != actual {
+        return Err(TestCaseError::fail(format!(
+            "replaying the emitted events produced a different state\n{}",
+            super::diff(&expected, &actual)
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{BankAccount, BankAccountCommand, DepositFunds, OpenAccount, WithdrawFunds};
+    use ::proptest::{prop_oneof, strategy::Just};
+
+    fn commands() -> impl Strategy<Value = BankAccountCommand> {
+        prop_oneof![
+            Just(BankAccountCommand::OpenAccount(OpenAccount {})),
+            (0u32..100).prop_map(|amount| BankAccountCommand::DepositFunds(DepositFunds { amount })),
+            (0u32..100).prop_map(|amount| BankAccountCommand::WithdrawFunds(WithdrawFunds { amount })),
+        ]
+    }
+
+    #[test]
+    fn test_balance_never_negative() {
+        CommandSequence::<BankAccount, _>::new(commands())
+            .invariant("balance never negative", |account| account.balance >= 0)
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "invariant `balance below 50` violated")]
+    fn test_invariant_violation() {
+        CommandSequence::<BankAccount, _>::new(commands())
+            .invariant("balance below 50", |account| account.balance < 50)
+            .run();
+    }
+}
@@ -0,0 +1,142 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::ItemStruct;
+
+pub struct DeriveAggregate {
+    ident: syn::Ident,
+    generics: syn::Generics,
+    command: syn::Path,
+    event: syn::Path,
+    name: String,
+    id_field: Option<syn::Ident>,
+}
+
+impl Parse for DeriveAggregate {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let item_struct: ItemStruct = input.parse()?;
+
+        let (mut command, mut event, mut name) = (None, None, None);
+        for attr in item_struct
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("aggregate"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("command") {
+                    command = Some(meta.value()?.parse::<syn::Path>()?);
+                } else if meta.path.is_ident("event") {
+                    event = Some(meta.value()?.parse::<syn::Path>()?);
+                } else if meta.path.is_ident("name") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    if value.value().is_empty() {
+                        return Err(syn::Error::new(value.span(), "name must not be empty"));
+                    }
+                    name = Some(value.value());
+                } else {
+                    return Err(meta.error("unsupported aggregate attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        let span = item_struct.ident.span();
+        let Some(command) = command else {
+            return Err(syn::Error::new(span, "missing `#[aggregate(command = ...)]`"));
+        };
+        let Some(event) = event else {
+            return Err(syn::Error::new(span, "missing `#[aggregate(event = ...)]`"));
+        };
+        // 型名はストリームに保存されるため、型の名前から推測せずに明示させる
+        let Some(name) = name else {
+            return Err(syn::Error::new(span, "missing `#[aggregate(name = \"...\")]`"));
+        };
+
+        // `#[aggregate(id)]` の付いたフィールド、なければ `id` フィールドに集約IDを設定する
+        let mut id_field = None;
+        if let syn::Fields::Named(fields) = &item_struct.fields {
+            for field in &fields.named {
+                let mut is_id = false;
+                for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("aggregate")) {
+                    attr.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("id") {
+                            is_id = true;
+                            Ok(())
+                        } else {
+                            Err(meta.error("unsupported aggregate field attribute"))
+                        }
+                    })?;
+                }
+                if is_id {
+                    if id_field.is_some() {
+                        return Err(syn::Error::new(field.span(), "only one field can be marked as id"));
+                    }
+                    id_field = field.ident.clone();
+                }
+            }
+            if id_field.is_none() {
+                id_field = fields
+                    .named
+                    .iter()
+                    .filter_map(|field| field.ident.clone())
+                    .find(|ident| ident == "id");
+            }
+        }
+
+        Ok(DeriveAggregate {
+            name,
+            ident: item_struct.ident,
+            generics: item_struct.generics,
+            command,
+            event,
+            id_field,
+        })
+    }
+}
+
+impl DeriveAggregate {
+    pub fn expand(self) -> TokenStream {
+        let Self {
+            ident,
+            generics,
+            command,
+            event,
+            name,
+            id_field,
+        } = self;
+
+        let init = match id_field {
+            Some(id_field) => quote! {
+                #ident {
+                    #id_field: ::std::convert::From::from(id),
+                    ..::std::default::Default::default()
+                }
+            },
+            None => quote! {
+                let _ = id;
+                ::std::default::Default::default()
+            },
+        };
+
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        // ジェネリクスを参照できるよう、派生の検査は init の中で行う
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics ::tsuzuri::aggregate::Aggregate for #ident #ty_generics #where_clause {
+                type Command = #command;
+                type Event = #event;
+
+                fn init(id: ::std::string::String) -> Self {
+                    ::tsuzuri::__macro_helpers::assert_derived_command::<#command>();
+                    ::tsuzuri::__macro_helpers::assert_derived_event::<#event>();
+                    #init
+                }
+
+                fn aggregate_type() -> &'static str {
+                    #name
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use proc_macro2::Span;
+
+/// Options of a `#[event(...)]` or `#[command(...)]` variant attribute.
+pub struct VariantAttr {
+    pub name: String,
+    pub version: u32,
+    /// Error type of the command, only accepted by `#[command(...)]`.
+    pub error: Option<syn::Type>,
+    pub span: Span,
+}
+
+impl VariantAttr {
+    /// Parses the `attr` attribute of the variant. `version` is only accepted when `allow_version` is set.
+    pub fn parse(variant: &syn::Variant, attr: &str, allow_version: bool) -> syn::Result<Self> {
+        let mut options = VariantAttr {
+            name: variant.ident.to_string(),
+            version: 1,
+            error: None,
+            span: variant.ident.span(),
+        };
+        for attribute in variant.attrs.iter().filter(|attribute| attribute.path().is_ident(attr)) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    let name: syn::LitStr = meta.value()?.parse()?;
+                    if name.value().is_empty() {
+                        return Err(syn::Error::new(name.span(), "name must not be empty"));
+                    }
+                    options.name = name.value();
+                    options.span = name.span();
+                    Ok(())
+                } else if allow_version && meta.path.is_ident("version") {
+                    let version: syn::LitInt = meta.value()?.parse()?;
+                    options.version = version.base10_parse()?;
+                    Ok(())
+                } else if attr == "command" && meta.path.is_ident("error") {
+                    options.error = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error(format!("unsupported {attr} attribute")))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+/// Errors on the first name used by more than one variant.
+pub fn check_duplicate_names<'a>(attrs: impl IntoIterator<Item = &'a VariantAttr>, kind: &str) -> syn::Result<()> {
+    let mut seen = HashMap::new();
+    for attr in attrs {
+        if let Some(first) = seen.insert(attr.name.as_str(), attr.span) {
+            let mut err = syn::Error::new(attr.span, format!("duplicate {kind} name `{}`", attr.name));
+            err.combine(syn::Error::new(first, "first used here"));
+            return Err(err);
+        }
+    }
+    Ok(())
+}
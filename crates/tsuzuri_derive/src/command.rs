@@ -1,107 +1,177 @@
-use std::collections::HashMap;
-
+use crate::attr::{check_duplicate_names, VariantAttr};
+use crate::generics::{aggregate_param, used_by, with_aggregate_param};
+use crate::serde_tag::serde_tags;
+use crate::variant::Variants;
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::parse::{Parse, ParseStream};
-use syn::spanned::Spanned;
 use syn::ItemEnum;
 
 pub struct DeriveCommand {
-    ident: syn::Ident,
+    commands: Variants,
     command_type: CommandType,
+    attrs: Vec<VariantAttr>,
+    tags: Vec<String>,
+    // バリアントごとのエラー型 (`handle_enum` では空)
+    errors: Vec<syn::Type>,
 }
 
 enum CommandType {
-    Unnamed(HashMap<syn::Ident, syn::Path>),
-    Other,
+    // 各バリアントを `Handle<Command>` に振り分ける
+    PerVariant,
+    // 集約が `Handle<Enum>` を実装する (`#[command(handle_enum)]`)
+    WholeEnum,
 }
 
 impl Parse for DeriveCommand {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let item_enum: ItemEnum = input.parse()?;
-        let mut commands = HashMap::new();
-        let mut is_unnamed = true;
-        for variant in item_enum.variants {
-            match variant.fields {
-                syn::Fields::Named(_) => {
-                    is_unnamed = false;
-                    break;
-                }
-                syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed, .. }) => {
-                    let span = unnamed.span();
-                    let mut iter = unnamed.into_iter();
-                    let Some(field) = iter.next() else {
-                        return Err(syn::Error::new(span, "command not specified"));
-                    };
-                    let syn::Type::Path(syn::TypePath { path, .. }) = field.ty else {
-                        return Err(syn::Error::new(span, "expected path to command"));
-                    };
-                    if iter.next().is_some() {
-                        return Err(syn::Error::new(span, "only one command can be specified"));
-                    }
-                    commands.insert(variant.ident, path);
+        let attrs = item_enum
+            .variants
+            .iter()
+            .map(|variant| VariantAttr::parse(variant, "command", false))
+            .collect::<syn::Result<Vec<_>>>()?;
+        check_duplicate_names(&attrs, "command")?;
+        let tags = serde_tags(&item_enum)?;
+
+        let (mut command_type, mut error) = (CommandType::PerVariant, None);
+        for attr in item_enum.attrs.iter().filter(|attr| attr.path().is_ident("command")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("handle_enum") {
+                    command_type = CommandType::WholeEnum;
+                    Ok(())
+                } else if meta.path.is_ident("error") {
+                    error = Some(meta.value()?.parse::<syn::Type>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported command attribute"))
                 }
-                syn::Fields::Unit => {
-                    is_unnamed = false;
-                    break;
+            })?;
+        }
+
+        // エラー型はバリアントの指定を優先し、なければ列挙型の指定を使う
+        let errors = match command_type {
+            CommandType::PerVariant => item_enum
+                .variants
+                .iter()
+                .zip(&attrs)
+                .map(|(variant, attr)| {
+                    attr.error.clone().or_else(|| error.clone()).ok_or_else(|| {
+                        syn::Error::new(
+                            variant.ident.span(),
+                            "missing `#[command(error = ...)]` on the enum or the variant, set it to the `Handle::Error` of the command",
+                        )
+                    })
+                })
+                .collect::<syn::Result<_>>()?,
+            CommandType::WholeEnum => {
+                if let Some(error) = attrs.iter().filter_map(|attr| attr.error.as_ref()).chain(&error).next() {
+                    return Err(syn::Error::new_spanned(
+                        error,
+                        "the error type is set by `Handle<Enum>` with `handle_enum`",
+                    ));
                 }
+                Vec::new()
             }
-        }
-        let command_type = if is_unnamed {
-            CommandType::Unnamed(commands)
-        } else {
-            CommandType::Other
         };
 
         Ok(DeriveCommand {
-            ident: item_enum.ident,
+            commands: Variants::parse(item_enum, "command")?,
             command_type,
+            attrs,
+            tags,
+            errors,
         })
     }
 }
 
 impl DeriveCommand {
     pub fn expand(self) -> TokenStream {
+        let command_structs = match self.command_type {
+            CommandType::PerVariant => self.commands.expand_structs("Commands", quote! {}),
+            CommandType::WholeEnum => quote! {},
+        };
+        let error_enum = self.expand_error_enum();
         let handle_impl = self.expand_handle_impl();
         let from_impls = self.expand_from_impls();
+        let command_type_impl = self.expand_command_type_impl();
+
+        let ident = &self.commands.ident;
+        let (impl_generics, ty_generics, where_clause) = self.commands.generics.split_for_impl();
 
         quote! {
+            #command_structs
+            #error_enum
             #handle_impl
             #from_impls
+            #command_type_impl
+            impl #impl_generics ::tsuzuri::__macro_helpers::DerivedCommand for #ident #ty_generics #where_clause {}
         }
     }
 
+    fn paths(&self) -> Vec<TokenStream> {
+        let commands = &self.commands;
+        commands
+            .variants
+            .iter()
+            .map(|(name, kind)| commands.path(name, kind))
+            .collect()
+    }
+
     fn expand_handle_impl(&self) -> TokenStream {
-        let Self { ident, command_type } = self;
+        let commands = &self.commands;
+        let ident = &commands.ident;
+        let agg = aggregate_param();
+        let (_, ty_generics, _) = commands.generics.split_for_impl();
+
+        match self.command_type {
+            CommandType::PerVariant if commands.variants.is_empty() => {
+                let generics = with_aggregate_param(&commands.generics, []);
+                let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+                quote! {
+                    #[automatically_derived]
+                    impl #impl_generics ::tsuzuri::aggregate::Handle<#ident #ty_generics> for ::tsuzuri::aggregate::State<#agg>
+                    #where_clause
+                    {
+                        type Error = ::std::convert::Infallible;
 
-        match command_type {
-            CommandType::Unnamed(commands) => {
-                let paths: Vec<_> = commands.values().collect();
-                let arms = commands.iter().map(|(name, path)| {
+                        fn handle(&self, cmd: #ident #ty_generics) -> ::std::result::Result<::std::vec::Vec<<#agg as ::tsuzuri::aggregate::Aggregate>::Event>, Self::Error> {
+                            match cmd {}
+                        }
+                    }
+                }
+            }
+            CommandType::PerVariant => {
+                let error = self.error_ident();
+                let binding = format_ident!("cmd");
+                let arms = commands.variants.iter().zip(self.paths()).map(|((name, kind), path)| {
+                    let (pattern, value) = commands.destructure(name, kind, &binding);
                     quote! {
-                        #ident::#name(cmd) => {
-                            <T as ::tsuzuri::aggregate::Handle<#path>>::handle(&self.0, cmd)
-                                .map_err(|err|
-                                    ::tsuzuri::__macro_helpers::serde_json::to_value(err)
-                                        .unwrap_or_else(|err|
-                                            ::tsuzuri::__macro_helpers::serde_json::Value::String(::std::string::ToString::to_string(&err))
-                                        )
-                                    )
+                        #pattern => {
+                            <#agg as ::tsuzuri::aggregate::Handle<#path>>::handle(&self.0, #value).map_err(#error::#name)
                         }
                     }
                 });
 
+                let bounds = self
+                    .paths()
+                    .into_iter()
+                    .zip(&self.errors)
+                    .map(|(path, error)| quote! { #agg: ::tsuzuri::aggregate::Handle<#path, Error = #error> });
+                let generics = with_aggregate_param(&commands.generics, bounds);
+                let (impl_generics, _, where_clause) = generics.split_for_impl();
+                let error_generics = self.error_generics();
+                let (_, error_ty_generics, _) = error_generics.split_for_impl();
+
                 quote! {
                     #[automatically_derived]
-                    impl<T> ::tsuzuri::aggregate::Handle<#ident> for ::tsuzuri::aggregate::State<T>
-                    where
-                        T: ::tsuzuri::aggregate::Aggregate,
-                        #( T: ::tsuzuri::aggregate::Handle<#paths>, )*
-                        #( <T as ::tsuzuri::aggregate::Handle<#paths>>::Error: ::serde::Serialize, )*
+                    impl #impl_generics ::tsuzuri::aggregate::Handle<#ident #ty_generics> for ::tsuzuri::aggregate::State<#agg>
+                    #where_clause
                     {
-                        type Error = ::tsuzuri::__macro_helpers::serde_json::Value;
+                        type Error = #error #error_ty_generics;
 
-                        fn handle(&self, cmd: #ident) -> ::std::result::Result<::std::vec::Vec<<T as ::tsuzuri::aggregate::Aggregate>::Event>, Self::Error> {
+                        fn handle(&self, cmd: #ident #ty_generics) -> ::std::result::Result<::std::vec::Vec<<#agg as ::tsuzuri::aggregate::Aggregate>::Event>, Self::Error> {
                             match cmd {
                                 #( #arms, )*
                             }
@@ -109,42 +179,252 @@ impl DeriveCommand {
                     }
                 }
             }
-            CommandType::Other => quote! {
-                impl<T> ::tsuzuri::aggregate::Handle<#ident> for ::tsuzuri::aggregate::State<T>
-                where
-                    T: ::tsuzuri::aggregate::Aggregate + ::tsuzuri::aggregate::Handle<#ident>,
-                {
-                    type Error = <T as ::tsuzuri::aggregate::Handle<#ident>>::Error;
-
-                    fn handle(&self, cmd: #ident) -> ::std::result::Result<::std::vec::Vec<<Self as ::tsuzuri::aggregate::Aggregate>::Event>, Self::Error> {
-                        <T as ::tsuzuri::aggregate::Handle<#ident>>::handle(&self.0, cmd)
+            CommandType::WholeEnum => {
+                let generics = with_aggregate_param(
+                    &commands.generics,
+                    [quote! { #agg: ::tsuzuri::aggregate::Handle<#ident #ty_generics> }],
+                );
+                let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+                quote! {
+                    impl #impl_generics ::tsuzuri::aggregate::Handle<#ident #ty_generics> for ::tsuzuri::aggregate::State<#agg>
+                    #where_clause
+                    {
+                        type Error = <#agg as ::tsuzuri::aggregate::Handle<#ident #ty_generics>>::Error;
+
+                        fn handle(&self, cmd: #ident #ty_generics) -> ::std::result::Result<::std::vec::Vec<<Self as ::tsuzuri::aggregate::Aggregate>::Event>, Self::Error> {
+                            <#agg as ::tsuzuri::aggregate::Handle<#ident #ty_generics>>::handle(&self.0, cmd)
+                        }
                     }
                 }
-            },
+            }
         }
     }
 
     fn expand_from_impls(&self) -> TokenStream {
-        let Self { ident, command_type } = self;
+        let CommandType::PerVariant = self.command_type else {
+            return quote! {};
+        };
+        let commands = &self.commands;
+        let ident = &commands.ident;
+        let binding = format_ident!("cmd");
+        let (impl_generics, ty_generics, where_clause) = commands.generics.split_for_impl();
 
-        match command_type {
-            CommandType::Unnamed(commands) => {
-                let from_impls = commands.iter().map(|(name, path)| {
-                    quote! {
-                        #[automatically_derived]
-                        impl ::std::convert::From<#path> for #ident {
-                            fn from(cmd: #path) -> Self {
-                                #ident::#name(cmd)
-                            }
+        let from_impls = commands.variants.iter().zip(self.paths()).map(|((name, kind), path)| {
+            let body = commands.construct(name, kind, &binding);
+            quote! {
+                #[automatically_derived]
+                impl #impl_generics ::std::convert::From<#path> for #ident #ty_generics #where_clause {
+                    fn from(cmd: #path) -> Self {
+                        #body
+                    }
+                }
+            }
+        });
+
+        quote! {
+            #( #from_impls )*
+        }
+    }
+
+    fn expand_command_type_impl(&self) -> TokenStream {
+        let Self {
+            commands, attrs, tags, ..
+        } = self;
+        let ident = &commands.ident;
+        let (impl_generics, ty_generics, where_clause) = commands.generics.split_for_impl();
+
+        let idents: Vec<_> = commands.variants.iter().map(|(name, _)| name).collect();
+        let names: Vec<_> = attrs.iter().map(|attr| &attr.name).collect();
+
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics ::tsuzuri::aggregate::CommandType for #ident #ty_generics #where_clause {
+                fn command_type(&self) -> &'static str {
+                    match *self {
+                        #( #ident::#idents { .. } => #names, )*
+                    }
+                }
+
+                fn variant_of(command_type: &str) -> ::std::option::Option<&'static str> {
+                    match command_type {
+                        #( #names => ::std::option::Option::Some(#tags), )*
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            }
+        }
+    }
+
+    fn error_ident(&self) -> syn::Ident {
+        format_ident!("{}Error", self.commands.ident)
+    }
+
+    // エラー列挙型は、エラー型で使われているジェネリクスだけを持つ
+    fn error_generics(&self) -> syn::Generics {
+        let errors = &self.errors;
+        used_by(&self.commands.generics, quote! { #( #errors )* })
+    }
+
+    fn expand_error_from_impls(&self) -> TokenStream {
+        let error = self.error_ident();
+        let generics = self.error_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let names: Vec<_> = self.commands.variants.iter().map(|(name, _)| name).collect();
+        let spellings: Vec<_> = self.errors.iter().map(|ty| quote!(#ty).to_string()).collect();
+        let keys: Vec<_> = self.errors.iter().map(last_segment).collect();
+
+        // 全コマンドが同じエラー型なら、列挙型からそのエラー型に変換する
+        if spellings.iter().all(|spelling| *spelling == spellings[0]) {
+            let inner = &self.errors[0];
+            // `impl<E> From<..> for E` は孤児ルールに反するため生成しない
+            if self.is_type_param(inner) {
+                return quote! {};
+            }
+            return quote! {
+                #[automatically_derived]
+                impl #impl_generics ::std::convert::From<#error #ty_generics> for #inner #where_clause {
+                    fn from(err: #error #ty_generics) -> Self {
+                        match err {
+                            #( #error::#names(err) => err, )*
                         }
                     }
-                });
+                }
+            };
+        }
 
+        // 複数のコマンドで共有されるエラー型は変換先が決まらないため、変換を生成しない。
+        // `String` と `std::string::String` のような別の書き方も同じ型として扱う
+        let from_impls = names
+            .iter()
+            .zip(&self.errors)
+            .zip(&keys)
+            .filter(|((_, inner), key)| {
+                keys.iter().filter(|other| other == key).count() == 1 && !self.is_type_param(inner)
+            })
+            .map(|((name, inner), _)| {
                 quote! {
-                    #( #from_impls )*
+                    #[automatically_derived]
+                    impl #impl_generics ::std::convert::From<#inner> for #error #ty_generics #where_clause {
+                        fn from(err: #inner) -> Self {
+                            #error::#name(err)
+                        }
+                    }
                 }
+            });
+        quote! {
+            #( #from_impls )*
+        }
+    }
+
+    fn is_type_param(&self, ty: &syn::Type) -> bool {
+        let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+            return false;
+        };
+        path.get_ident()
+            .is_some_and(|ident| self.commands.generics.type_params().any(|param| param.ident == *ident))
+    }
+
+    fn expand_error_enum(&self) -> TokenStream {
+        let Self { commands, .. } = self;
+        let CommandType::PerVariant = self.command_type else {
+            return quote! {};
+        };
+        if commands.variants.is_empty() {
+            return quote! {};
+        }
+        let (ident, vis) = (&commands.ident, &commands.vis);
+
+        let error = self.error_ident();
+        let generics = self.error_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        let names: Vec<_> = commands.variants.iter().map(|(name, _)| name).collect();
+        let errors = &self.errors;
+        // 具体的な型の境界が満たされない場合も impl を省くだけになるよう、高階の境界にする
+        let bounded = |bound: TokenStream| {
+            let mut generics = generics.clone();
+            let where_clause = generics.make_where_clause();
+            for error in errors {
+                where_clause
+                    .predicates
+                    .push(syn::parse_quote!(for<'__tsuzuri> #error: #bound));
+            }
+            generics.where_clause.unwrap()
+        };
+        let from_impls = self.expand_error_from_impls();
+        // Debug, Serialize, Display は各コマンドのエラーをそのまま使う
+        let debug_where = bounded(quote! { ::std::fmt::Debug });
+        let display_where = bounded(quote! { ::std::fmt::Display });
+        let error_where = bounded(quote! { ::std::error::Error + 'static });
+        let serialize_where = bounded(quote! { ::serde::Serialize });
+
+        quote! {
+            #[doc = concat!("Error returned when handling a [`", stringify!(#ident), "`], with one variant per command.")]
+            #vis enum #error #generics #where_clause {
+                #(
+                    #[doc = concat!("Rejection of [`", stringify!(#ident), "::", stringify!(#names), "`].")]
+                    #names(#errors),
+                )*
             }
-            CommandType::Other => quote! {},
+
+            #[automatically_derived]
+            impl #impl_generics ::std::fmt::Debug for #error #ty_generics #debug_where {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    match self {
+                        #( #error::#names(err) => f.debug_tuple(stringify!(#names)).field(err).finish(), )*
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl #impl_generics ::std::fmt::Display for #error #ty_generics #display_where {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    match self {
+                        #( #error::#names(err) => ::std::fmt::Display::fmt(err, f), )*
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl #impl_generics ::std::error::Error for #error #ty_generics #error_where {
+                fn source(&self) -> ::std::option::Option<&::tsuzuri::__macro_helpers::DynError> {
+                    match self {
+                        #( #error::#names(err) => ::std::error::Error::source(err), )*
+                    }
+                }
+            }
+
+            #[automatically_derived]
+            impl #impl_generics ::serde::Serialize for #error #ty_generics #serialize_where {
+                fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+                    match self {
+                        #( #error::#names(err) => ::serde::Serialize::serialize(err, serializer), )*
+                    }
+                }
+            }
+
+            #from_impls
+
+            #[automatically_derived]
+            impl #impl_generics ::std::convert::From<#error #ty_generics> for ::tsuzuri::__macro_helpers::serde_json::Value #serialize_where {
+                fn from(err: #error #ty_generics) -> Self {
+                    ::tsuzuri::__macro_helpers::serde_json::to_value(&err).unwrap_or_else(|_| {
+                        ::tsuzuri::__macro_helpers::serde_json::Value::String(::std::format!("{:?}", err))
+                    })
+                }
+            }
+        }
+    }
+}
+
+// 型の最後のセグメントを比較のキーにする。パスの書き方が違っても同じ型とみなす
+fn last_segment(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => {
+            let segment = path.segments.last();
+            quote!(#segment).to_string()
         }
+        ty => quote!(#ty).to_string(),
     }
 }
@@ -1,92 +1,89 @@
-use std::collections::HashMap;
-
+use crate::attr::{check_duplicate_names, VariantAttr};
+use crate::generics::{aggregate_param, with_aggregate_param};
+use crate::serde_tag::serde_tags;
+use crate::variant::Variants;
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::parse::{Parse, ParseStream};
-use syn::spanned::Spanned;
 use syn::ItemEnum;
 
 pub struct DeriveEvent {
-    ident: syn::Ident,
-    events: HashMap<syn::Ident, syn::Path>,
+    events: Variants,
+    attrs: Vec<VariantAttr>,
+    tags: Vec<String>,
 }
 
 impl Parse for DeriveEvent {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let item_enum: ItemEnum = input.parse()?;
-        let events = item_enum
+        let attrs = item_enum
             .variants
-            .into_iter()
-            .map(|variant| {
-                let name = variant.ident;
-                let path = match variant.fields {
-                    syn::Fields::Named(_) => {
-                        return Err(syn::Error::new(
-                            variant.fields.span(),
-                            "event must be an unnamed field".to_string(),
-                        ));
-                    }
-                    syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed, .. }) => {
-                        let span = unnamed.span();
-                        let mut iter = unnamed.into_iter();
-                        let Some(field) = iter.next() else {
-                            return Err(syn::Error::new(span, "event not specified"));
-                        };
-                        let syn::Type::Path(syn::TypePath { path, .. }) = field.ty else {
-                            return Err(syn::Error::new(span, "expected path to event"));
-                        };
-                        if iter.next().is_some() {
-                            return Err(syn::Error::new(span, "only one event can be specified"));
-                        }
-                        path
-                    }
-                    syn::Fields::Unit => {
-                        return Err(syn::Error::new(
-                            variant.fields.span(),
-                            "inner event type must be specified".to_string(),
-                        ));
-                    }
-                };
-                Ok((name, path))
-            })
-            .collect::<Result<_, _>>()?;
+            .iter()
+            .map(|variant| VariantAttr::parse(variant, "event", true))
+            .collect::<syn::Result<Vec<_>>>()?;
+        check_duplicate_names(&attrs, "event")?;
+        let tags = serde_tags(&item_enum)?;
 
         Ok(DeriveEvent {
-            ident: item_enum.ident,
-            events,
+            events: Variants::parse(item_enum, "event")?,
+            attrs,
+            tags,
         })
     }
 }
 
 impl DeriveEvent {
     pub fn expand(self) -> TokenStream {
+        let event_structs = self.events.expand_structs("Events", quote! { #[derive(Clone, Debug)] });
         let apply_impl = self.expand_apply_impl();
         let from_impls = self.expand_from_impls();
+        let event_type_impl = self.expand_event_type_impl();
+
+        let ident = &self.events.ident;
+        let (impl_generics, ty_generics, where_clause) = self.events.generics.split_for_impl();
 
         quote! {
+            #event_structs
             #apply_impl
             #from_impls
+            #event_type_impl
+            impl #impl_generics ::tsuzuri::__macro_helpers::DerivedEvent for #ident #ty_generics #where_clause {}
         }
     }
 
     fn expand_apply_impl(&self) -> TokenStream {
-        let Self { ident, events, .. } = self;
+        let events = &self.events;
+        let ident = &events.ident;
+        let agg = aggregate_param();
+        let binding = format_ident!("event");
 
-        let paths = events.values();
-        let arms = events.iter().map(|(name, path)| {
+        let paths: Vec<_> = events
+            .variants
+            .iter()
+            .map(|(name, kind)| events.path(name, kind))
+            .collect();
+        let arms = events.variants.iter().zip(&paths).map(|((name, kind), path)| {
+            let (pattern, value) = events.destructure(name, kind, &binding);
             quote! {
-                #ident::#name(event) => <T as ::tsuzuri::aggregate::Apply<#path>>::apply(&mut self.0, event)
+                #pattern => <#agg as ::tsuzuri::aggregate::Apply<#path>>::apply(&mut self.0, #value)
             }
         });
 
+        let generics = with_aggregate_param(
+            &events.generics,
+            paths
+                .iter()
+                .map(|path| quote! { #agg: ::tsuzuri::aggregate::Apply<#path> }),
+        );
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        let (_, ty_generics, _) = events.generics.split_for_impl();
+
         quote! {
             #[automatically_derived]
-            impl<T> ::tsuzuri::aggregate::Apply<#ident> for ::tsuzuri::aggregate::State<T>
-            where
-                T: ::tsuzuri::aggregate::Aggregate,
-                #( T: ::tsuzuri::aggregate::Apply<#paths>, )*
+            impl #impl_generics ::tsuzuri::aggregate::Apply<#ident #ty_generics> for ::tsuzuri::aggregate::State<#agg>
+            #where_clause
             {
-                fn apply(&mut self, event: #ident) {
+                fn apply(&mut self, event: #ident #ty_generics) {
                     match event {
                         #( #arms, )*
                     }
@@ -96,14 +93,19 @@ impl DeriveEvent {
     }
 
     fn expand_from_impls(&self) -> TokenStream {
-        let Self { ident, events, .. } = self;
+        let events = &self.events;
+        let ident = &events.ident;
+        let binding = format_ident!("event");
+        let (impl_generics, ty_generics, where_clause) = events.generics.split_for_impl();
 
-        let from_impls = events.iter().map(|(name, path)| {
+        let from_impls = events.variants.iter().map(|(name, kind)| {
+            let path = events.path(name, kind);
+            let body = events.construct(name, kind, &binding);
             quote! {
                 #[automatically_derived]
-                impl ::std::convert::From<#path> for #ident {
+                impl #impl_generics ::std::convert::From<#path> for #ident #ty_generics #where_clause {
                     fn from(event: #path) -> Self {
-                        #ident::#name(event)
+                        #body
                     }
                 }
             }
@@ -113,4 +115,38 @@ impl DeriveEvent {
             #( #from_impls )*
         }
     }
+
+    fn expand_event_type_impl(&self) -> TokenStream {
+        let Self { events, attrs, tags } = self;
+        let ident = &events.ident;
+        let (impl_generics, ty_generics, where_clause) = events.generics.split_for_impl();
+
+        let variants: Vec<_> = events.variants.iter().map(|(name, _)| name).collect();
+        let names: Vec<_> = attrs.iter().map(|attr| &attr.name).collect();
+        let versions = attrs.iter().map(|attr| attr.version);
+
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics ::tsuzuri::aggregate::EventType for #ident #ty_generics #where_clause {
+                fn event_type(&self) -> &'static str {
+                    match *self {
+                        #( #ident::#variants { .. } => #names, )*
+                    }
+                }
+
+                fn event_version(&self) -> u32 {
+                    match *self {
+                        #( #ident::#variants { .. } => #versions, )*
+                    }
+                }
+
+                fn variant_of(event_type: &str) -> ::std::option::Option<&'static str> {
+                    match event_type {
+                        #( #names => ::std::option::Option::Some(#tags), )*
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_quote, Generics};
+
+/// Type parameter of the aggregate in the generated `State<_>` impls.
+///
+/// The name avoids clashing with the generic parameters of the derived enum.
+pub fn aggregate_param() -> syn::Ident {
+    format_ident!("__TsuzuriAggregate")
+}
+
+/// Adds the aggregate type parameter and the given bounds to the generics of the enum.
+pub fn with_aggregate_param(generics: &Generics, bounds: impl IntoIterator<Item = TokenStream>) -> Generics {
+    let param = aggregate_param();
+    let mut generics = generics.clone();
+    generics.params.push(parse_quote!(#param));
+    let where_clause = generics.make_where_clause();
+    where_clause
+        .predicates
+        .push(parse_quote!(#param: ::tsuzuri::aggregate::Aggregate));
+    for bound in bounds {
+        where_clause.predicates.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+/// Keeps the generic parameters and where predicates used by `tokens`.
+///
+/// Used for the structs generated from variant fields, which cannot declare
+/// unused parameters.
+pub fn used_by(generics: &Generics, tokens: impl ToTokens) -> Generics {
+    let used = idents(tokens.to_token_stream());
+    let name = |param: &syn::GenericParam| match param {
+        syn::GenericParam::Lifetime(param) => param.lifetime.ident.to_string(),
+        syn::GenericParam::Type(param) => param.ident.to_string(),
+        syn::GenericParam::Const(param) => param.ident.to_string(),
+    };
+    let dropped: HashSet<_> = generics
+        .params
+        .iter()
+        .map(name)
+        .filter(|name| !used.contains(name))
+        .collect();
+
+    let mut kept = generics.clone();
+    kept.params = generics
+        .params
+        .iter()
+        .filter(|param| !dropped.contains(&name(param)))
+        .cloned()
+        .collect();
+    if let Some(where_clause) = &mut kept.where_clause {
+        where_clause.predicates = where_clause
+            .predicates
+            .iter()
+            .filter(|predicate| idents(quote!(#predicate)).is_disjoint(&dropped))
+            .cloned()
+            .collect();
+    }
+    kept
+}
+
+fn idents(tokens: TokenStream) -> HashSet<String> {
+    let mut idents = HashSet::new();
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => idents.extend(self::idents(group.stream())),
+            _ => {}
+        }
+    }
+    idents
+}
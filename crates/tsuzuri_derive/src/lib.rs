@@ -1,21 +1,77 @@
+use aggregate::DeriveAggregate;
 use command::DeriveCommand;
 use event::DeriveEvent;
 
+mod aggregate;
+mod attr;
 mod command;
 mod event;
+mod generics;
+mod serde_tag;
+mod variant;
 
+extern crate heck;
 extern crate proc_macro2;
 extern crate quote;
 extern crate syn;
 
+/// Used to implement `Aggregate` for a struct.
+///
+/// `#[aggregate(command = ..., event = ..., name = "...")]` sets the command and
+/// event types, which must derive `Command` and `Event`, and the aggregate type
+/// name stored in every stream id, which is required. `init` uses `Default`, setting the
+/// field marked `#[aggregate(id)]`, or else the `id` field, to the aggregate id.
+#[proc_macro_derive(Aggregate, attributes(aggregate))]
+pub fn aggregate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    syn::parse_macro_input!(input as DeriveAggregate).expand().into()
+}
+
 /// Used to implement traits for an aggregate command enum.
-#[proc_macro_derive(Command)]
+///
+/// Each variant is dispatched to `Handle<Command>` on the aggregate. Variants
+/// wrapping a type dispatch to that type, while struct-like and unit variants
+/// get a struct of the same name generated in a snake_case module named after
+/// the enum, e.g. `BankAccountCommand::CloseAccount { reason }` dispatches to
+/// `Handle<bank_account_command::CloseAccount>`. With `#[command(handle_enum)]`
+/// on the enum, the aggregate implements `Handle<Enum>` itself instead.
+///
+/// Rejections are returned as a generated `{Enum}Error` enum with one variant per
+/// command, wrapping the error type set with `#[command(error = ...)]` on the enum,
+/// or on a variant to override it. It implements `Debug`, `Display`, `std::error::Error`
+/// and `Serialize` through the inner errors, and converts into `serde_json::Value`.
+/// When every command shares the error type, the enum converts into it with `From`.
+/// Otherwise each inner error converts into the enum, except error types shared
+/// by several commands. Types are compared by their last path segment, so
+/// `String` and `std::string::String` are the same type, but an alias must be
+/// spelled the same way as the type it names. No `From` impl is generated for
+/// an error type that is a type parameter of the enum.
+///
+/// Migrating from the `{Enum}Error<A>` enum generic over the aggregate: add
+/// `#[command(error = ...)]` with the `Handle::Error` type of the commands, on
+/// the enum or on each variant whose error differs, and remove the aggregate
+/// argument, e.g. `BankAccountCommandError::<BankAccount>` becomes
+/// `BankAccountCommandError`. Enums with `#[command(handle_enum)]` are unchanged.
+///
+/// `#[command(name = "...")]` on a variant sets the name returned by
+/// `CommandType::command_type` and accepted by the command bus. As with events,
+/// the enum must be externally tagged and serde renames are translated.
+#[proc_macro_derive(Command, attributes(command))]
 pub fn command(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     syn::parse_macro_input!(input as DeriveCommand).expand().into()
 }
 
 /// Used to implement traits for an aggregate event enum.
-#[proc_macro_derive(Event)]
+///
+/// Variants wrapping an event type dispatch to `Apply<Event>`. Struct-like and
+/// unit variants get a struct of the same name generated in a snake_case module
+/// named after the enum, e.g. `BankAccountEvent::AccountClosed { reason }`
+/// dispatches to `Apply<bank_account_event::AccountClosed>`.
+///
+/// `#[event(name = "...", version = N)]` on a variant sets the name the event is
+/// stored under and its version. They default to the variant identifier and 1.
+/// The enum must be externally tagged; `#[serde(rename)]` and `#[serde(rename_all)]`
+/// are translated to and from the stored names.
+#[proc_macro_derive(Event, attributes(event))]
 pub fn event(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     syn::parse_macro_input!(input as DeriveEvent).expand().into()
 }
@@ -0,0 +1,121 @@
+use proc_macro2::TokenStream;
+use syn::meta::ParseNestedMeta;
+use syn::ItemEnum;
+
+/// Returns the tag serde uses for each variant of the enum.
+///
+/// Stored names are translated to these tags when decoding, so `#[serde(rename)]`
+/// and `#[serde(rename_all)]` are honored. Enums which are not externally tagged
+/// are rejected, as their tag cannot be replaced by the stored name.
+pub fn serde_tags(item_enum: &ItemEnum) -> syn::Result<Vec<String>> {
+    let mut rename_all = None;
+    for attr in item_enum.attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") || meta.path.is_ident("content") || meta.path.is_ident("untagged") {
+                Err(meta.error("only externally tagged enums are supported, remove this serde attribute"))
+            } else if meta.path.is_ident("rename_all") {
+                if !meta.input.peek(syn::Token![=]) {
+                    return Err(meta.error("use `rename_all = \"...\"` for both serialization and deserialization"));
+                }
+                let rule: syn::LitStr = meta.value()?.parse()?;
+                rename_all = Some(RenameRule::parse(&rule)?);
+                Ok(())
+            } else {
+                skip(&meta)
+            }
+        })?;
+    }
+
+    item_enum
+        .variants
+        .iter()
+        .map(|variant| {
+            let mut tag = None;
+            for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("untagged") {
+                        Err(meta.error("untagged variants are not supported"))
+                    } else if meta.path.is_ident("rename") {
+                        if !meta.input.peek(syn::Token![=]) {
+                            return Err(meta.error("use `rename = \"...\"` for both serialization and deserialization"));
+                        }
+                        let name: syn::LitStr = meta.value()?.parse()?;
+                        tag = Some(name.value());
+                        Ok(())
+                    } else {
+                        skip(&meta)
+                    }
+                })?;
+            }
+            let ident = variant.ident.to_string();
+            Ok(tag.unwrap_or_else(|| match rename_all {
+                Some(rule) => rule.apply(&ident),
+                None => ident,
+            }))
+        })
+        .collect()
+}
+
+// 関係のない serde の属性は値ごと読み飛ばす
+fn skip(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<TokenStream>()?;
+    }
+    Ok(())
+}
+
+/// `rename_all` rules, applied to variants the same way as serde.
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(rule: &syn::LitStr) -> syn::Result<Self> {
+        Ok(match rule.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return Err(syn::Error::new(rule.span(), "unknown rename rule")),
+        })
+    }
+
+    fn apply(self, variant: &str) -> String {
+        match self {
+            Self::Lower => variant.to_ascii_lowercase(),
+            Self::Upper => variant.to_ascii_uppercase(),
+            Self::Pascal => variant.to_string(),
+            Self::Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            Self::Snake => {
+                // heck とは異なり、serde は大文字ごとに区切る
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            Self::ScreamingSnake => Self::Snake.apply(variant).to_ascii_uppercase(),
+            Self::Kebab => Self::Snake.apply(variant).replace('_', "-"),
+            Self::ScreamingKebab => Self::ScreamingSnake.apply(variant).replace('_', "-"),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::generics::used_by;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::ItemEnum;
+
+/// Shape of an enum variant.
+pub enum VariantKind {
+    // `Variant(Type)`
+    Path(syn::Path),
+    // `Variant { field: Type }`
+    Named(Vec<syn::Field>),
+    // `Variant`
+    Unit,
+}
+
+/// Variants of a derived enum.
+///
+/// Struct-like and unit variants are backed by structs of the same name,
+/// generated in a snake_case module named after the enum, e.g.
+/// `BankAccountEvent::AccountClosed { reason }` is backed by
+/// `bank_account_event::AccountClosed`.
+pub struct Variants {
+    pub ident: syn::Ident,
+    pub vis: syn::Visibility,
+    pub generics: syn::Generics,
+    pub variants: Vec<(syn::Ident, VariantKind)>,
+}
+
+impl Variants {
+    /// Parses the variants. `kind` names the wrapped types in error messages.
+    pub fn parse(item_enum: ItemEnum, kind: &str) -> syn::Result<Self> {
+        let variants = item_enum
+            .variants
+            .into_iter()
+            .map(|variant| {
+                let variant_kind = match variant.fields {
+                    syn::Fields::Named(syn::FieldsNamed { named, .. }) => {
+                        VariantKind::Named(named.into_iter().collect())
+                    }
+                    syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed, .. }) => {
+                        let span = unnamed.span();
+                        let mut iter = unnamed.into_iter();
+                        let Some(field) = iter.next() else {
+                            return Err(syn::Error::new(span, format!("{kind} not specified")));
+                        };
+                        let syn::Type::Path(syn::TypePath { path, .. }) = field.ty else {
+                            return Err(syn::Error::new(span, format!("expected path to {kind}")));
+                        };
+                        if iter.next().is_some() {
+                            return Err(syn::Error::new(span, format!("only one {kind} can be specified")));
+                        }
+                        VariantKind::Path(path)
+                    }
+                    syn::Fields::Unit => VariantKind::Unit,
+                };
+                Ok((variant.ident, variant_kind))
+            })
+            .collect::<syn::Result<_>>()?;
+
+        Ok(Variants {
+            ident: item_enum.ident,
+            vis: item_enum.vis,
+            generics: item_enum.generics,
+            variants,
+        })
+    }
+
+    pub fn module(&self) -> syn::Ident {
+        format_ident!("{}", self.ident.to_string().to_snake_case())
+    }
+
+    // 生成する構造体は、フィールドで使われているジェネリクスだけを持つ
+    fn struct_generics(&self, kind: &VariantKind) -> syn::Generics {
+        match kind {
+            VariantKind::Named(fields) => {
+                let types = fields.iter().map(|field| &field.ty);
+                used_by(&self.generics, quote! { #( #types )* })
+            }
+            VariantKind::Path(_) | VariantKind::Unit => syn::Generics::default(),
+        }
+    }
+
+    /// Returns the type backing the variant.
+    pub fn path(&self, name: &syn::Ident, kind: &VariantKind) -> TokenStream {
+        match kind {
+            VariantKind::Path(path) => quote! { #path },
+            VariantKind::Named(_) | VariantKind::Unit => {
+                let module = self.module();
+                let generics = self.struct_generics(kind);
+                let (_, ty_generics, _) = generics.split_for_impl();
+                quote! { #module::#name #ty_generics }
+            }
+        }
+    }
+
+    /// Generates the module of structs backing struct-like and unit variants.
+    ///
+    /// `derives` is applied to the structs of struct-like variants, as their fields
+    /// may not implement every trait.
+    pub fn expand_structs(&self, noun: &str, derives: TokenStream) -> TokenStream {
+        let Self {
+            ident, vis, variants, ..
+        } = self;
+
+        let structs: Vec<_> = variants
+            .iter()
+            .filter_map(|(name, kind)| match kind {
+                VariantKind::Path(_) => None,
+                VariantKind::Named(fields) => {
+                    let generics = self.struct_generics(kind);
+                    let where_clause = &generics.where_clause;
+                    let fields = fields.iter().map(|field| {
+                        let docs = field.attrs.iter().filter(|attr| attr.path().is_ident("doc"));
+                        let (name, ty) = (&field.ident, &field.ty);
+                        quote! { #( #docs )* pub #name: #ty }
+                    });
+                    Some(quote! {
+                        #[doc = concat!("Fields of [`", stringify!(#ident), "::", stringify!(#name), "`].")]
+                        #derives
+                        pub struct #name #generics #where_clause {
+                            #( #fields, )*
+                        }
+                    })
+                }
+                VariantKind::Unit => Some(quote! {
+                    #[doc = concat!("Marker for [`", stringify!(#ident), "::", stringify!(#name), "`].")]
+                    #[derive(Clone, Copy, Debug, Default)]
+                    pub struct #name;
+                }),
+            })
+            .collect();
+
+        if structs.is_empty() {
+            return quote! {};
+        }
+
+        let module = self.module();
+        quote! {
+            #[doc = concat!(#noun, " generated from the struct and unit variants of [`", stringify!(#ident), "`].")]
+            #[allow(unused_imports)]
+            #vis mod #module {
+                use super::*;
+
+                #( #structs )*
+            }
+        }
+    }
+
+    /// Returns a pattern matching the variant and an expression of the backing type built from it.
+    pub fn destructure(
+        &self,
+        name: &syn::Ident,
+        kind: &VariantKind,
+        binding: &syn::Ident,
+    ) -> (TokenStream, TokenStream) {
+        let ident = &self.ident;
+        let module = self.module();
+        match kind {
+            VariantKind::Path(_) => (quote! { #ident::#name(#binding) }, quote! { #binding }),
+            VariantKind::Named(fields) => {
+                let fields: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+                (
+                    quote! { #ident::#name { #( #fields ),* } },
+                    quote! { #module::#name { #( #fields ),* } },
+                )
+            }
+            VariantKind::Unit => (quote! { #ident::#name }, quote! { #module::#name }),
+        }
+    }
+
+    /// Returns statements building the variant from `binding` of the backing type.
+    pub fn construct(&self, name: &syn::Ident, kind: &VariantKind, binding: &syn::Ident) -> TokenStream {
+        let ident = &self.ident;
+        let module = self.module();
+        match kind {
+            VariantKind::Path(_) => quote! { #ident::#name(#binding) },
+            VariantKind::Named(fields) => {
+                let fields: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+                quote! {
+                    let #module::#name { #( #fields ),* } = #binding;
+                    #ident::#name { #( #fields ),* }
+                }
+            }
+            VariantKind::Unit => quote! {
+                let _ = #binding;
+                #ident::#name
+            },
+        }
+    }
+}
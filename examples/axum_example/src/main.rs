@@ -1,22 +1,36 @@
-use axum::{routing::get, Router};
+//! Serves the bank account domain over HTTP.
+//!
+//! ```sh
+//! curl -X POST localhost:3100/BankAccount/1/commands -H 'content-type: application/json' -d '{"OpenAccount": {}}'
+//! curl -X POST localhost:3100/BankAccount/1/commands -H 'content-type: application/json' -d '{"DepositFunds": {"amount": 100}}'
+//! curl -X POST localhost:3100/BankAccount/1/commands -H 'content-type: application/json' -d '{"WithdrawFunds": {"amount": 500}}'
+//! curl localhost:3100/BankAccount/1/events
+//! ```
+
+mod bank_account;
+
+use bank_account::BankAccount;
+use std::sync::Arc;
+use tsuzuri::{
+    store::sync::{event_store::EventStore, memory_store::MemoryStore},
+    TsuzuriBuilder,
+};
 
 #[tokio::main]
 async fn main() {
     // initialize tracing
     tracing_subscriber::fmt::init();
 
-    // build our application with a route
-    let app = Router::new()
-        // `GET /` goes to `root`
-        .route("/", get(root));
+    // build tsuzuri with the aggregates served over HTTP
+    let tsuzuri = TsuzuriBuilder::new(EventStore::new(MemoryStore::new()))
+        .register::<BankAccount>()
+        .build();
+
+    // `POST /{aggregate}/{id}/commands` and `GET /{aggregate}/{id}/events`
+    let app = tsuzuri::axum::router(Arc::new(tsuzuri));
 
     // run our app with hyper
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3100").await.unwrap();
     tracing::debug!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app).await.unwrap();
 }
-
-// basic handler that responds with a static string
-async fn root() -> &'static str {
-    "Hello, World!"
-}